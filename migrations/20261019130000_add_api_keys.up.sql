@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+CREATE TYPE api_key_tier_enum AS ENUM ('basic', 'extended', 'unlimited');
+
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    tier api_key_tier_enum NOT NULL DEFAULT 'basic',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP FUNCTION IF EXISTS take_rate_limit_token;
+
+DROP TABLE IF EXISTS rate_limit_buckets;
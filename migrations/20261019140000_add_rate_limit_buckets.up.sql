@@ -0,0 +1,50 @@
+-- Add up migration script here
+
+-- rate limit state is short-lived, so losing it on a crash is fine
+CREATE UNLOGGED TABLE rate_limit_buckets (
+    key TEXT PRIMARY KEY,
+    tokens DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    -- time at which the bucket is full again, after which the row can be removed
+    full_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_rate_limit_buckets_full_at ON rate_limit_buckets (full_at);
+
+-- Take a token from a bucket holding at most `burst_size` tokens, refilled with one token per
+-- `period_seconds`, returning whether a token was available and how many tokens are left.
+CREATE FUNCTION take_rate_limit_token(
+    bucket_key TEXT,
+    burst_size INTEGER,
+    period_seconds DOUBLE PRECISION,
+    OUT allowed BOOLEAN,
+    OUT remaining DOUBLE PRECISION
+)
+LANGUAGE plpgsql AS $$
+DECLARE
+    -- the time the row lock was acquired, not the start of the transaction
+    now TIMESTAMPTZ;
+BEGIN
+    INSERT INTO rate_limit_buckets (key, tokens, updated_at, full_at)
+    VALUES (bucket_key, burst_size, clock_timestamp(), clock_timestamp())
+    ON CONFLICT (key) DO NOTHING;
+
+    PERFORM 1 FROM rate_limit_buckets WHERE key = bucket_key FOR UPDATE;
+    now := clock_timestamp();
+
+    SELECT LEAST(burst_size, tokens + EXTRACT(EPOCH FROM now - updated_at) / period_seconds)
+    INTO remaining
+    FROM rate_limit_buckets WHERE key = bucket_key;
+
+    allowed := remaining >= 1;
+    IF allowed THEN
+        remaining := remaining - 1;
+    END IF;
+
+    UPDATE rate_limit_buckets
+    SET tokens = remaining,
+        updated_at = now,
+        full_at = now + make_interval(secs => (burst_size - remaining) * period_seconds)
+    WHERE key = bucket_key;
+END;
+$$;
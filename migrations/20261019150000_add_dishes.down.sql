@@ -0,0 +1,35 @@
+-- Add down migration script here
+
+DROP VIEW IF EXISTS meals_view;
+
+CREATE VIEW meals_view AS
+SELECT
+    id,
+    date,
+    canteen,
+    name,
+    dish_type,
+    image_src,
+    price_students,
+    price_employees,
+    price_guests,
+    vegan,
+    vegetarian,
+    kjoules,
+    proteins,
+    carbohydrates,
+    fats,
+    round(kjoules / 4.184) AS kcal
+FROM meals
+WHERE is_latest = TRUE;
+
+DROP TRIGGER IF EXISTS meals_link_dish ON meals;
+
+DROP FUNCTION IF EXISTS link_meal_to_dish;
+
+ALTER TABLE meals
+DROP COLUMN IF EXISTS dish_id;
+
+DROP TABLE IF EXISTS dishes;
+
+DROP FUNCTION IF EXISTS normalize_dish_name;
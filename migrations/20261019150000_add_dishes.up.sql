@@ -0,0 +1,73 @@
+-- Add up migration script here
+
+-- Normalize a dish name so that spellings differing only in casing, punctuation or whitespace
+-- refer to the same dish.
+CREATE FUNCTION normalize_dish_name(name TEXT) RETURNS TEXT
+LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$
+    SELECT lower(btrim(regexp_replace(name, '[[:punct:][:space:]]+', ' ', 'g')))
+$$;
+
+CREATE TABLE dishes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    normalized_name TEXT NOT NULL UNIQUE,
+    -- the spelling the dish was first seen with
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO dishes (normalized_name, name)
+SELECT DISTINCT ON (normalize_dish_name(name)) normalize_dish_name(name), name
+FROM meals
+ORDER BY normalize_dish_name(name), date, refreshed_at;
+
+ALTER TABLE meals
+ADD COLUMN dish_id UUID REFERENCES dishes(id);
+
+UPDATE meals
+SET dish_id = dishes.id
+FROM dishes
+WHERE dishes.normalized_name = normalize_dish_name(meals.name);
+
+ALTER TABLE meals
+ALTER COLUMN dish_id SET NOT NULL;
+
+CREATE INDEX idx_meals_dish_id_date ON meals(dish_id, date);
+
+-- Link every inserted meal to its dish, adding the dish to the catalog if it is new.
+CREATE FUNCTION link_meal_to_dish() RETURNS TRIGGER
+LANGUAGE plpgsql AS $$
+BEGIN
+    INSERT INTO dishes (normalized_name, name)
+    VALUES (normalize_dish_name(NEW.name), NEW.name)
+    ON CONFLICT (normalized_name) DO NOTHING;
+
+    SELECT id INTO NEW.dish_id FROM dishes WHERE normalized_name = normalize_dish_name(NEW.name);
+    RETURN NEW;
+END;
+$$;
+
+CREATE TRIGGER meals_link_dish
+BEFORE INSERT OR UPDATE OF name ON meals
+FOR EACH ROW EXECUTE FUNCTION link_meal_to_dish();
+
+CREATE OR REPLACE VIEW meals_view AS
+SELECT
+    id,
+    date,
+    canteen,
+    name,
+    dish_type,
+    image_src,
+    price_students,
+    price_employees,
+    price_guests,
+    vegan,
+    vegetarian,
+    kjoules,
+    proteins,
+    carbohydrates,
+    fats,
+    round(kjoules / 4.184) AS kcal,
+    dish_id
+FROM meals
+WHERE is_latest = TRUE;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_meals_name_trgm ON meals USING GIN (name gin_trgm_ops);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+CREATE INDEX idx_dishes_normalized_name_prefix ON dishes (normalized_name text_pattern_ops);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE meals
+DROP COLUMN IF EXISTS replaced_at;
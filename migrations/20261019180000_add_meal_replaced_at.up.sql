@@ -0,0 +1,22 @@
+-- Add up migration script here
+
+ALTER TABLE meals
+ADD COLUMN replaced_at TIMESTAMPTZ;
+
+-- Dishes replaced before the column existed are assumed to be replaced by the next scrape of the
+-- canteen that inserted dishes, or otherwise by the next scrape of the canteen at all.
+UPDATE meals m
+SET replaced_at = COALESCE(
+    (
+        SELECT MIN(n.refreshed_at)
+        FROM meals n
+        WHERE n.date = m.date AND n.canteen = m.canteen AND n.refreshed_at > m.refreshed_at
+    ),
+    (
+        SELECT MIN(s.scraped_at)
+        FROM canteens_scraped s
+        WHERE s.scraped_for = m.date AND s.canteen = m.canteen AND s.scraped_at > m.refreshed_at
+    ),
+    m.refreshed_at
+)
+WHERE is_latest = FALSE;
@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS meals_update_dish_popularity ON meals;
+
+DROP FUNCTION IF EXISTS update_dish_popularity;
+
+DROP TABLE IF EXISTS dish_popularity;
+
+DROP FUNCTION IF EXISTS dish_serving_weight;
@@ -0,0 +1,57 @@
+-- Add down migration script here
+
+-- Weight of a serving for autocompletion. Every serving counts `1/e` as much as one 90 days later,
+-- so ranking by the sum of the weights of all servings equals ranking by servings decayed to the
+-- current day, without having to recompute the sums every day.
+CREATE FUNCTION dish_serving_weight(date DATE) RETURNS FLOAT8
+LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE AS $$
+    SELECT exp((date - DATE '2020-01-01') / 90.0::float8)
+$$;
+
+CREATE TABLE dish_popularity (
+    dish_id UUID PRIMARY KEY REFERENCES dishes(id) ON DELETE CASCADE,
+    servings INTEGER NOT NULL,
+    score FLOAT8 NOT NULL
+);
+
+INSERT INTO dish_popularity (dish_id, servings, score)
+SELECT dish_id, COUNT(*), SUM(dish_serving_weight(date))
+FROM meals
+WHERE is_latest = TRUE
+GROUP BY dish_id;
+
+-- Keep the popularity of dishes up to date with their latest servings.
+CREATE FUNCTION update_dish_popularity() RETURNS TRIGGER
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        IF OLD.is_latest = NEW.is_latest AND OLD.dish_id = NEW.dish_id AND OLD.date = NEW.date THEN
+            RETURN NULL;
+        END IF;
+    END IF;
+
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        IF OLD.is_latest THEN
+            UPDATE dish_popularity
+            SET servings = servings - 1, score = score - dish_serving_weight(OLD.date)
+            WHERE dish_id = OLD.dish_id;
+        END IF;
+    END IF;
+
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        IF NEW.is_latest THEN
+            INSERT INTO dish_popularity (dish_id, servings, score)
+            VALUES (NEW.dish_id, 1, dish_serving_weight(NEW.date))
+            ON CONFLICT (dish_id) DO UPDATE
+            SET servings = dish_popularity.servings + 1, score = dish_popularity.score + EXCLUDED.score;
+        END IF;
+    END IF;
+
+    RETURN NULL;
+END;
+$$;
+
+-- `name` is listed as `dish_id` is set by the `meals_link_dish` trigger when the name changes
+CREATE TRIGGER meals_update_dish_popularity
+AFTER INSERT OR DELETE OR UPDATE OF is_latest, dish_id, date, name ON meals
+FOR EACH ROW EXECUTE FUNCTION update_dish_popularity();
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+-- The popularity of dishes is computed when suggesting them instead, relative to the current day.
+DROP TRIGGER IF EXISTS meals_update_dish_popularity ON meals;
+
+DROP FUNCTION IF EXISTS update_dish_popularity;
+
+DROP TABLE IF EXISTS dish_popularity;
+
+DROP FUNCTION IF EXISTS dish_serving_weight;
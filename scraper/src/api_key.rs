@@ -0,0 +1,77 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::Rng as _;
+use sha2::{Digest as _, Sha256};
+use shared::ApiKeyTier;
+use sqlx::{PgPool, types::Uuid};
+
+/// Prefix of all generated API keys, making them recognizable e.g. for secret scanners.
+const KEY_PREFIX: &str = "mupb_";
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    /// The first characters of the key, to identify it without revealing it.
+    pub key_prefix: String,
+    pub tier: ApiKeyTier,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Hash an API key for storage and lookup.
+///
+/// Keys are generated with enough entropy that a fast hash is sufficient.
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Generate and store a new API key, returning its id and the key itself.
+///
+/// Only the hash of the key is stored, so it cannot be retrieved afterwards.
+pub async fn create_api_key(db: &PgPool, name: &str, tier: ApiKeyTier) -> Result<(Uuid, String)> {
+    let secret = rand::rng()
+        .random::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let key = format!("{KEY_PREFIX}{secret}");
+
+    let id = sqlx::query_scalar!(
+        "INSERT INTO api_keys (name, key_prefix, key_hash, tier) VALUES ($1, $2, $3, $4) RETURNING id",
+        name,
+        &key[..KEY_PREFIX.len() + 8],
+        hash_api_key(&key),
+        tier as ApiKeyTier,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok((id, key))
+}
+
+pub async fn list_api_keys(db: &PgPool) -> Result<Vec<ApiKey>> {
+    let keys = sqlx::query_as!(
+        ApiKey,
+        r#"SELECT id, name, key_prefix, tier AS "tier: ApiKeyTier", created_at, revoked_at FROM api_keys ORDER BY created_at"#
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(keys)
+}
+
+/// Revoke an API key, returning whether an active key with the given id existed.
+pub async fn revoke_api_key(db: &PgPool, id: Uuid) -> Result<bool> {
+    let res = sqlx::query!(
+        "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
@@ -1,26 +1,100 @@
 use anyhow::Result;
 use clap::Parser;
 use futures::future;
-use mensa_upb_scraper::check_refresh;
-use sqlx::postgres::PgPoolOptions;
+use mensa_upb_scraper::{
+    api_key, check_refresh,
+    config::{self, Config, ConfigArgs, Overrides},
+    telemetry, webhook,
+};
+use shared::ApiKeyTier;
+use sqlx::{PgPool, postgres::PgPoolOptions, types::Uuid};
 use strum::IntoEnumIterator as _;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Debug, Clone, clap::Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Database connection string
-    #[clap(env = "DATABASE_URL")]
-    database: String,
+    /// Database connection string, overriding the configuration
+    database: Option<String>,
     /// Canteen to scrape
     #[clap(short, long = "canteen")]
     canteens: Vec<shared::Canteen>,
     /// Date to scrape (YYYY-MM-DD)
-    #[clap(short, long = "date", required = true)]
+    #[clap(short, long = "date", required_unless_present = "print_config")]
     dates: Vec<chrono::NaiveDate>,
     /// Force refresh even if not needed
     #[clap(short, long)]
     force: bool,
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum Command {
+    /// Manage webhooks notified about menu changes
+    #[command(subcommand)]
+    Webhook(WebhookCommand),
+    /// Manage API keys
+    #[command(subcommand)]
+    ApiKey(ApiKeyCommand),
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum ApiKeyCommand {
+    /// Create a new API key
+    Create {
+        /// Name to identify the key by, e.g. the service using it
+        name: String,
+        /// Quota tier of the key (basic, extended or unlimited)
+        #[clap(short, long, default_value_t = ApiKeyTier::Basic)]
+        tier: ApiKeyTier,
+    },
+    /// List all API keys
+    List,
+    /// Revoke an API key
+    Revoke {
+        /// ID of the key
+        id: Uuid,
+    },
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum WebhookCommand {
+    /// Register a new webhook
+    Add {
+        /// URL the payloads are sent to
+        url: String,
+        /// Secret used to sign the payloads
+        #[clap(short, long, env = "WEBHOOK_SECRET")]
+        secret: String,
+        /// Canteen to receive changes for (all canteens if omitted)
+        #[clap(short, long = "canteen")]
+        canteens: Vec<shared::Canteen>,
+        /// Only deliver if an added dish matches this case-insensitive regex
+        #[clap(short, long)]
+        pattern: Option<String>,
+    },
+    /// List all registered webhooks
+    List,
+    /// Remove a webhook and its delivery log
+    Remove {
+        /// ID of the webhook
+        id: Uuid,
+    },
+    /// Show the delivery log
+    Deliveries {
+        /// Only show deliveries of this webhook
+        #[clap(short, long)]
+        webhook: Option<Uuid>,
+        /// Maximum number of deliveries to show
+        #[clap(short, long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Send all deliveries that are due
+    Deliver,
 }
 
 #[tokio::main]
@@ -29,32 +103,155 @@ async fn main() -> Result<()> {
 
     let mut cli = Cli::parse();
 
+    let config = Config::load(
+        &cli.config,
+        Overrides {
+            database_url: cli.database.clone(),
+            ..Default::default()
+        },
+    )?;
+    if cli.config.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+    let config = config::init(config);
+
     if cli.canteens.is_empty() {
         cli.canteens = shared::Canteen::iter().collect();
     }
 
-    let db = PgPoolOptions::new().connect_lazy(&cli.database)?;
+    let db = PgPoolOptions::new().connect_lazy(&config.database_url)?;
 
     let env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::WARN.into())
         .from_env()
         .expect("Invalid filter")
         .add_directive("mensa_upb_scraper=debug".parse().unwrap());
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    let _telemetry = telemetry::init_tracing(
+        "scraper-cli",
+        env_filter,
+        config.telemetry.otlp_endpoint.as_deref(),
+    )?;
 
     sqlx::migrate!("../migrations").run(&db).await?;
 
+    match cli.command {
+        Some(Command::Webhook(command)) => return run_webhook_command(&db, command).await,
+        Some(Command::ApiKey(command)) => return run_api_key_command(&db, command).await,
+        None => {}
+    }
+
     tracing::info!("Starting up...");
 
     let handles = cli.dates.into_iter().map(|date| {
         let db = db.clone();
         let canteens = cli.canteens.clone();
-        tokio::spawn(async move { check_refresh(&db, date, &canteens, cli.force).await })
+        tokio::spawn(async move {
+            check_refresh(&db, date, &canteens, &config.filter_canteens, cli.force).await
+        })
     });
 
     future::join_all(handles).await;
 
     tracing::info!("Finished scraping menu");
 
+    webhook::deliver_pending(&db).await?;
+
+    Ok(())
+}
+
+async fn run_webhook_command(db: &PgPool, command: WebhookCommand) -> Result<()> {
+    match command {
+        WebhookCommand::Add {
+            url,
+            secret,
+            canteens,
+            pattern,
+        } => {
+            let id = webhook::add_webhook(db, &url, &secret, &canteens, pattern.as_deref()).await?;
+            println!("{id}");
+        }
+        WebhookCommand::List => {
+            for webhook in webhook::list_webhooks(db).await? {
+                println!(
+                    "{}\t{}\tcanteens: {}\tpattern: {}\tcreated: {}",
+                    webhook.id,
+                    webhook.url,
+                    if webhook.canteens.is_empty() {
+                        "all".to_string()
+                    } else {
+                        webhook
+                            .canteens
+                            .iter()
+                            .map(|c| c.get_identifier())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    },
+                    webhook.dish_pattern.as_deref().unwrap_or("-"),
+                    webhook.created_at,
+                );
+            }
+        }
+        WebhookCommand::Remove { id } => {
+            if !webhook::remove_webhook(db, id).await? {
+                anyhow::bail!("No webhook with id {id}");
+            }
+        }
+        WebhookCommand::Deliveries { webhook, limit } => {
+            for delivery in webhook::list_deliveries(db, webhook, limit).await? {
+                println!(
+                    "{}\twebhook: {}\t{:?}\tattempts: {}\tcreated: {}\tdelivered: {}\tstatus code: {}\terror: {}",
+                    delivery.id,
+                    delivery.webhook_id,
+                    delivery.status,
+                    delivery.attempts,
+                    delivery.created_at,
+                    delivery
+                        .delivered_at
+                        .map_or_else(|| "-".to_string(), |d| d.to_string()),
+                    delivery
+                        .last_status_code
+                        .map_or_else(|| "-".to_string(), |c| c.to_string()),
+                    delivery.last_error.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+        WebhookCommand::Deliver => {
+            let delivered = webhook::deliver_pending(db).await?;
+            println!("Delivered {delivered} payloads");
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_api_key_command(db: &PgPool, command: ApiKeyCommand) -> Result<()> {
+    match command {
+        ApiKeyCommand::Create { name, tier } => {
+            let (id, key) = api_key::create_api_key(db, &name, tier).await?;
+            println!("Created API key {id}, it cannot be shown again:");
+            println!("{key}");
+        }
+        ApiKeyCommand::List => {
+            for key in api_key::list_api_keys(db).await? {
+                println!(
+                    "{}\t{}\t{}...\ttier: {}\tcreated: {}\trevoked: {}",
+                    key.id,
+                    key.name,
+                    key.key_prefix,
+                    key.tier,
+                    key.created_at,
+                    key.revoked_at
+                        .map_or_else(|| "-".to_string(), |d| d.to_string()),
+                );
+            }
+        }
+        ApiKeyCommand::Revoke { id } => {
+            if !api_key::revoke_api_key(db, id).await? {
+                anyhow::bail!("No active API key with id {id}");
+            }
+        }
+    }
+
     Ok(())
 }
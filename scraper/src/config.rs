@@ -0,0 +1,377 @@
+//! Configuration shared by the API, the scraper and the CLI.
+//!
+//! Values are layered, later sources taking precedence: defaults, the TOML configuration file,
+//! environment variables (see [`ENV_VARS`]) and command line flags.
+
+use std::{
+    fmt,
+    net::IpAddr,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Context as _, Result, bail};
+use figment::{
+    Figment,
+    providers::{Env, Format as _, Serialized, Toml},
+};
+use ipnet::IpNet;
+use reqwest::Url;
+use serde::{Deserialize, Deserializer, Serialize, de};
+use shared::Canteen;
+
+/// File read if no configuration file is given explicitly and it exists.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Environment variables overriding configuration values, with the path of the value they set.
+pub const ENV_VARS: &[(&str, &str)] = &[
+    ("DATABASE_URL", "database_url"),
+    ("FILTER_CANTEENS", "filter_canteens"),
+    ("API_INTERFACE", "api.interface"),
+    ("API_PORT", "api.port"),
+    ("API_CORS_ALLOWED", "api.cors_allowed"),
+    ("API_MENU_CACHE_SIZE", "api.menu_cache_size"),
+    ("API_MENU_CACHE_TTL_SECONDS", "api.menu_cache_ttl_seconds"),
+    (
+        "API_HEALTH_MAX_MENU_AGE_HOURS",
+        "api.health_max_menu_age_hours",
+    ),
+    ("API_TRUSTED_PROXIES", "api.trusted_proxies"),
+    ("API_METRICS_ALLOWED", "api.metrics_allowed"),
+    ("API_RATE_LIMIT_SECONDS", "api.rate_limit.seconds"),
+    ("API_RATE_LIMIT_BURST", "api.rate_limit.burst"),
+    ("API_RATE_LIMIT_BACKEND", "api.rate_limit.backend"),
+    ("API_RATE_LIMIT_EXEMPT", "api.rate_limit.exempt"),
+    ("API_RATE_LIMIT_IPV6_PREFIX", "api.rate_limit.ipv6_prefix"),
+    ("METRICS_PUSHGATEWAY_URL", "metrics.pushgateway_url"),
+    ("OTEL_EXPORTER_OTLP_ENDPOINT", "telemetry.otlp_endpoint"),
+];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Connection string of the Postgres database.
+    pub database_url: String,
+    /// Canteens that are neither scraped nor reported by the health checks.
+    #[serde(default, deserialize_with = "list")]
+    pub filter_canteens: Vec<Canteen>,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Interface the API listens on.
+    pub interface: String,
+    /// Port the API listens on.
+    pub port: u16,
+    /// Origins allowed to make CORS requests, `*` allows any origin.
+    #[serde(deserialize_with = "list")]
+    pub cors_allowed: Vec<String>,
+    /// Maximum number of menus kept in the in-memory cache.
+    pub menu_cache_size: NonZeroUsize,
+    /// Time in seconds after which a cached menu is queried again.
+    pub menu_cache_ttl_seconds: u64,
+    /// Age in hours after which the menu of the current day is reported as stale.
+    pub health_max_menu_age_hours: u32,
+    /// Reverse proxies whose `Forwarded`/`X-Forwarded-For` headers are trusted.
+    #[serde(deserialize_with = "networks")]
+    pub trusted_proxies: Vec<IpNet>,
+    /// Clients allowed to read `/metrics`, nobody if empty.
+    #[serde(deserialize_with = "networks")]
+    pub metrics_allowed: Vec<IpNet>,
+    pub rate_limit: RateLimitConfig,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            interface: "127.0.0.1".to_string(),
+            port: 8080,
+            cors_allowed: Vec::new(),
+            menu_cache_size: NonZeroUsize::new(256).unwrap(),
+            menu_cache_ttl_seconds: 300,
+            health_max_menu_age_hours: 12,
+            trusted_proxies: Vec::new(),
+            metrics_allowed: Vec::new(),
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Time in seconds after which a single request of the burst is replenished.
+    pub seconds: u64,
+    /// Maximum number of requests that can be made in a burst.
+    pub burst: u32,
+    pub backend: RateLimitBackend,
+    /// Clients that are never rate limited, e.g. monitoring.
+    #[serde(deserialize_with = "networks")]
+    pub exempt: Vec<IpNet>,
+    /// Length of the IPv6 prefix that is rate limited as a single client.
+    pub ipv6_prefix: u8,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            seconds: 5,
+            burst: 20,
+            backend: RateLimitBackend::Memory,
+            exempt: Vec::new(),
+            ipv6_prefix: 56,
+        }
+    }
+}
+
+/// Where the state of the rate limits is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitBackend {
+    /// In process memory, so every replica of the API limits requests on its own.
+    Memory,
+    /// In the database, so all replicas share the same limits.
+    Postgres,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Pushgateway the scraper pushes its metrics to after each run.
+    #[serde(deserialize_with = "non_empty")]
+    pub pushgateway_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP endpoint spans are exported to.
+    #[serde(deserialize_with = "non_empty")]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// Command line flags selecting the configuration file.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigArgs {
+    /// Path of the configuration file [default: config.toml, if it exists]
+    #[arg(long = "config", env = "MENSA_UPB_CONFIG", global = true)]
+    pub path: Option<PathBuf>,
+    /// Print the effective configuration and exit
+    #[arg(long, global = true)]
+    pub print_config: bool,
+}
+
+/// Configuration values given as command line flags, which take precedence over all other
+/// sources.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Overrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_url: Option<String>,
+    pub api: ApiOverrides,
+}
+
+#[derive(Debug, Clone, Default, Serialize, clap::Args)]
+pub struct ApiOverrides {
+    /// Interface the API listens on
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Port the API listens on
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+impl Config {
+    /// Load and validate the configuration from all sources.
+    pub fn load(args: &ConfigArgs, overrides: Overrides) -> Result<Self> {
+        let file = match &args.path {
+            Some(path) if !path.is_file() => {
+                bail!("Configuration file {} does not exist", path.display())
+            }
+            Some(path) => Some(path.as_path()),
+            None => Some(Path::new(DEFAULT_CONFIG_FILE)).filter(|path| path.is_file()),
+        };
+
+        let mut figment = Figment::new();
+        if let Some(file) = file {
+            figment = figment.merge(Toml::file_exact(file));
+        }
+        let config = figment
+            .merge(Env::raw().filter_map(|key| {
+                ENV_VARS
+                    .iter()
+                    .find(|(var, _)| key == *var)
+                    .map(|(_, path)| (*path).into())
+            }))
+            .merge(Serialized::defaults(overrides))
+            .extract::<Self>()
+            .map_err(|err| {
+                anyhow::anyhow!(
+                    "Invalid configuration:\n{}",
+                    err.into_iter()
+                        .map(|err| format!("  - {err}"))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                )
+            })?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        match Url::parse(&self.database_url) {
+            Ok(url) if matches!(url.scheme(), "postgres" | "postgresql") => {}
+            Ok(url) => errors.push(format!(
+                "database_url: unsupported scheme {:?}, expected postgres",
+                url.scheme()
+            )),
+            Err(err) => errors.push(format!("database_url: {err}")),
+        }
+        if self.api.interface.trim().is_empty() {
+            errors.push("api.interface: must not be empty".to_string());
+        }
+        if self.api.health_max_menu_age_hours == 0 {
+            errors.push("api.health_max_menu_age_hours: must be at least 1".to_string());
+        }
+        if self.api.rate_limit.seconds == 0 {
+            errors.push("api.rate_limit.seconds: must be at least 1".to_string());
+        }
+        if self.api.rate_limit.burst == 0 {
+            errors.push("api.rate_limit.burst: must be at least 1".to_string());
+        }
+        if self.api.rate_limit.ipv6_prefix > 128 {
+            errors.push(format!(
+                "api.rate_limit.ipv6_prefix: {} is longer than an IPv6 address",
+                self.api.rate_limit.ipv6_prefix
+            ));
+        }
+        for (name, url) in [
+            ("metrics.pushgateway_url", &self.metrics.pushgateway_url),
+            ("telemetry.otlp_endpoint", &self.telemetry.otlp_endpoint),
+        ] {
+            if let Some(url) = url {
+                match Url::parse(url) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                    Ok(url) => errors.push(format!(
+                        "{name}: unsupported scheme {:?}, expected http or https",
+                        url.scheme()
+                    )),
+                    Err(err) => errors.push(format!("{name}: {err}")),
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!("Invalid configuration:\n  - {}", errors.join("\n  - "))
+        }
+    }
+
+    /// Render the configuration as TOML, without the password of the database.
+    pub fn to_toml(&self) -> Result<String> {
+        let mut config = self.clone();
+        if let Ok(mut url) = Url::parse(&config.database_url)
+            && url.password().is_some()
+        {
+            url.set_password(Some("********")).ok();
+            config.database_url = url.to_string();
+        }
+
+        toml::to_string_pretty(&config).context("Failed to serialize configuration")
+    }
+}
+
+/// Make the configuration available via [`get`].
+pub fn init(config: Config) -> &'static Config {
+    CONFIG.get_or_init(|| config)
+}
+
+/// Get the configuration loaded at startup.
+///
+/// # Panics
+/// If [`init`] was not called yet.
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("configuration is not loaded")
+}
+
+/// Deserialize a list given either as a sequence or as a comma separated string, which is how
+/// lists are passed as environment variables.
+fn list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List<T> {
+        Items(Vec<T>),
+        CommaSeparated(String),
+    }
+
+    match List::<T>::deserialize(deserializer)? {
+        List::Items(items) => Ok(items),
+        List::CommaSeparated(s) => s
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| T::deserialize(de::value::StrDeserializer::<D::Error>::new(item)))
+            .collect(),
+    }
+}
+
+/// Deserialize a list of networks, given as CIDRs or single IP addresses.
+fn networks<'de, D>(deserializer: D) -> Result<Vec<IpNet>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    list::<D, Network>(deserializer).map(|nets| nets.into_iter().map(|net| net.0).collect())
+}
+
+struct Network(IpNet);
+
+impl<'de> Deserialize<'de> for Network {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NetworkVisitor;
+
+        impl de::Visitor<'_> for NetworkVisitor {
+            type Value = Network;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a CIDR or an IP address")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Network, E> {
+                v.parse::<IpNet>()
+                    .or_else(|_| v.parse::<IpAddr>().map(IpNet::from))
+                    .map(Network)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(NetworkVisitor)
+    }
+}
+
+/// Deserialize an optional string, treating an empty string as absent.
+fn non_empty<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|s| !s.is_empty()))
+}
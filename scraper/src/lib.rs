@@ -1,15 +1,19 @@
+pub mod api_key;
 mod canteen;
+pub mod config;
 mod dish;
 mod menu;
+pub mod metrics;
 mod refresh;
+pub mod telemetry;
 pub mod util;
+pub mod webhook;
 
-use std::{collections::HashSet, error::Error, fmt::Display, sync::LazyLock};
+use std::{error::Error, fmt::Display};
 
 pub use dish::Dish;
 pub use menu::scrape_menu;
-pub use refresh::check_refresh;
-use shared::Canteen;
+pub use refresh::{UPCOMING_DAYS, check_refresh, today, upcoming_days};
 
 #[derive(Debug, Clone)]
 struct CustomError(String);
@@ -34,13 +38,3 @@ impl From<String> for CustomError {
     }
 }
 
-pub static FILTER_CANTEENS: LazyLock<HashSet<Canteen>> = LazyLock::new(|| {
-    std::env::var("FILTER_CANTEENS")
-        .ok()
-        .map(|s| {
-            s.split(',')
-                .filter_map(|el| el.parse::<Canteen>().ok())
-                .collect::<HashSet<_>>()
-        })
-        .unwrap_or_default()
-});
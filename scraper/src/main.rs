@@ -1,24 +1,36 @@
-use std::sync::LazyLock;
-
 use anyhow::Result;
-use chrono::{Duration, Utc};
+use clap::Parser;
 use futures::future;
-use mensa_upb_scraper::{FILTER_CANTEENS, check_refresh, util};
+use mensa_upb_scraper::{
+    check_refresh,
+    config::{self, Config, ConfigArgs, Overrides},
+    metrics, telemetry, upcoming_days, util, webhook,
+};
 use shared::Canteen;
 use strum::IntoEnumIterator as _;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
-static CANTEENS: LazyLock<Vec<Canteen>> = LazyLock::new(|| {
-    Canteen::iter()
-        .filter(|c| !FILTER_CANTEENS.contains(c))
-        .collect::<Vec<_>>()
-});
+/// Scrape the menus of the upcoming week and notify webhooks about changes
+#[derive(Debug, Clone, clap::Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
 
+    let cli = Cli::parse();
+    let config = Config::load(&cli.config, Overrides::default())?;
+    if cli.config.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+    let config = config::init(config);
+
     let db = util::get_db()?;
 
     let env_filter = EnvFilter::builder()
@@ -26,22 +38,47 @@ async fn main() -> Result<()> {
         .from_env()
         .expect("Invalid filter")
         .add_directive("mensa_upb_scraper=debug".parse().unwrap());
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    let _telemetry = telemetry::init_tracing(
+        "mensa-upb-scraper",
+        env_filter,
+        config.telemetry.otlp_endpoint.as_deref(),
+    )?;
+
+    let pushgateway_url = config.metrics.pushgateway_url.as_deref();
+    let metrics_handle = if pushgateway_url.is_some() {
+        Some(metrics::install_recorder()?)
+    } else {
+        None
+    };
 
     sqlx::migrate!("../migrations").run(&db).await?;
 
     tracing::info!("Starting up...");
 
-    let handles = (0..7)
-        .map(|d| (Utc::now() + Duration::days(d)).date_naive())
-        .map(|date| {
-            let db = db.clone();
-            tokio::spawn(async move { check_refresh(&db, date, &CANTEENS, false).await })
-        });
+    let canteens = Canteen::iter()
+        .filter(|c| !config.filter_canteens.contains(c))
+        .collect::<Vec<_>>();
+    let handles = upcoming_days().map(|date| {
+        let db = db.clone();
+        let canteens = canteens.clone();
+        tokio::spawn(async move {
+            check_refresh(&db, date, &canteens, &config.filter_canteens, false).await
+        })
+    });
 
     future::join_all(handles).await;
 
     tracing::info!("Finished scraping menu");
 
+    let delivered = webhook::deliver_pending(&db).await?;
+    tracing::info!("Delivered {delivered} webhook payloads");
+
+    if let (Some(url), Some(handle)) = (pushgateway_url, metrics_handle) {
+        match metrics::push_metrics(&handle, url, "mensa-upb-scraper").await {
+            Ok(()) => tracing::info!("Pushed metrics to {url}"),
+            Err(err) => tracing::error!("Failed to push metrics to {url}: {err}"),
+        }
+    }
+
     Ok(())
 }
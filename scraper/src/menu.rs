@@ -5,7 +5,7 @@ use chrono::NaiveDate;
 use scraper::{Html, Selector};
 use shared::{Canteen, DishType};
 
-use crate::{CustomError, Dish, canteen::CanteenExt as _};
+use crate::{CustomError, Dish, canteen::CanteenExt as _, metrics};
 
 static HTML_MAIN_DISHES_TBODY_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
     Selector::parse("table.table-dishes.main-dishes > tbody").expect("Failed to parse selector")
@@ -27,19 +27,33 @@ pub async fn scrape_menu(date: &NaiveDate, canteen: Canteen) -> Result<Vec<Dish>
         "tx_pamensa_mensa[date]",
         date.format("%Y-%m-%d").to_string(),
     )]);
-    let response = request_builder.send().await?;
-    let html_content = response.text().await?;
+    let html_content = match fetch(request_builder).await {
+        Ok(html_content) => {
+            metrics::record_page_fetch(canteen, true);
+            html_content
+        }
+        Err(err) => {
+            metrics::record_page_fetch(canteen, false);
+            return Err(err.into());
+        }
+    };
 
     let document = scraper::Html::parse_document(&html_content);
 
-    let main_dishes = scrape_category(&document, &HTML_MAIN_DISHES_TBODY_SELECTOR, DishType::Main)?;
-    let side_dishes = scrape_category(&document, &HTML_SIDE_DISHES_TBODY_SELECTOR, DishType::Side)?;
-    let desserts = scrape_category(&document, &HTML_DESSERTS_TBODY_SELECTOR, DishType::Dessert)?;
+    let res = [
+        (&*HTML_MAIN_DISHES_TBODY_SELECTOR, DishType::Main),
+        (&*HTML_SIDE_DISHES_TBODY_SELECTOR, DishType::Side),
+        (&*HTML_DESSERTS_TBODY_SELECTOR, DishType::Dessert),
+    ]
+    .into_iter()
+    .map(|(selector, dish_type)| scrape_category(&document, selector, dish_type, canteen))
+    .collect::<Result<Vec<_>>>()
+    .inspect_err(|_| metrics::record_parse_failure(canteen, "page"))?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
 
-    let mut res = Vec::new();
-    res.extend(main_dishes);
-    res.extend(side_dishes);
-    res.extend(desserts);
+    metrics::record_dishes_parsed(canteen, res.len());
 
     tracing::debug!("Finished scraping");
 
@@ -53,10 +67,15 @@ static ITEM_DETAILS_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
     Selector::parse("tr.even > td.more > div.ingredients-list").expect("Failed to parse selector")
 });
 
+async fn fetch(request_builder: reqwest::RequestBuilder) -> reqwest::Result<String> {
+    request_builder.send().await?.text().await
+}
+
 fn scrape_category<'a>(
     document: &'a Html,
     tbody_selector: &Selector,
     dish_type: DishType,
+    canteen: Canteen,
 ) -> Result<impl Iterator<Item = Dish> + 'a> {
     let tbody = document.select(tbody_selector).next().ok_or_else(|| {
         CustomError::from(format!("No tbody found for selector: {:?}", tbody_selector))
@@ -64,7 +83,11 @@ fn scrape_category<'a>(
     let dishes = tbody.select(&ITEM_SELECTOR);
     let dish_details = tbody.select(&ITEM_DETAILS_SELECTOR);
 
-    Ok(dishes
-        .zip(dish_details)
-        .filter_map(move |(dish, details)| Dish::from_element(dish, details, dish_type)))
+    Ok(dishes.zip(dish_details).filter_map(move |(dish, details)| {
+        let dish = Dish::from_element(dish, details, dish_type);
+        if dish.is_none() {
+            metrics::record_parse_failure(canteen, "dish");
+        }
+        dish
+    }))
 }
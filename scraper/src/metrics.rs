@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use metrics::{
+    Unit, counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use shared::Canteen;
+
+const PAGES_FETCHED: &str = "scraper_pages_fetched_total";
+const DISHES_PARSED: &str = "scraper_dishes_parsed_total";
+const PARSE_FAILURES: &str = "scraper_parse_failures_total";
+const LAST_SUCCESS: &str = "scraper_last_success_timestamp_seconds";
+const REFRESHES: &str = "menu_refreshes_total";
+const REFRESH_DURATION: &str = "menu_refresh_duration_seconds";
+const STALE_DISHES: &str = "menu_stale_dishes_total";
+const NEW_DISHES: &str = "menu_new_dishes_total";
+
+/// Buckets of all histograms measuring durations, in seconds.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Install a recorder collecting the metrics of the scraper (and anything else recording
+/// metrics) in the Prometheus format.
+///
+/// Histograms ending in `_duration_seconds` are recorded with fixed buckets.
+pub fn install_recorder() -> Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("_duration_seconds".to_string()),
+            DURATION_BUCKETS,
+        )?
+        .install_recorder()?;
+
+    describe_counter!(
+        PAGES_FETCHED,
+        "Menu pages requested from the canteen website, by canteen and result"
+    );
+    describe_counter!(DISHES_PARSED, "Dishes parsed from menu pages, by canteen");
+    describe_counter!(
+        PARSE_FAILURES,
+        "Menu pages or dishes that could not be parsed, by canteen and kind"
+    );
+    describe_gauge!(
+        LAST_SUCCESS,
+        Unit::Seconds,
+        "Unix timestamp of the last menu page that was fetched and parsed, by canteen"
+    );
+    describe_counter!(REFRESHES, "Menu refreshes, by result");
+    describe_histogram!(
+        REFRESH_DURATION,
+        Unit::Seconds,
+        "Time it took to refresh menus"
+    );
+    describe_counter!(STALE_DISHES, "Dishes removed from menus by refreshes");
+    describe_counter!(NEW_DISHES, "Dishes added to menus by refreshes");
+
+    Ok(handle)
+}
+
+/// Push the collected metrics to a Prometheus Pushgateway, replacing the metrics previously
+/// pushed by this job.
+pub async fn push_metrics(
+    handle: &PrometheusHandle,
+    pushgateway_url: &str,
+    job: &str,
+) -> Result<()> {
+    let url = format!(
+        "{}/metrics/job/{job}",
+        pushgateway_url.trim_end_matches('/')
+    );
+
+    reqwest::Client::new()
+        .put(url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(handle.render())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+pub(crate) fn record_page_fetch(canteen: Canteen, success: bool) {
+    let result = if success { "success" } else { "error" };
+    counter!(PAGES_FETCHED, "canteen" => canteen.get_identifier().to_string(), "result" => result)
+        .increment(1);
+}
+
+/// Record that a menu page (`kind` is `page`) or a single dish (`kind` is `dish`) could not be
+/// parsed.
+pub(crate) fn record_parse_failure(canteen: Canteen, kind: &'static str) {
+    counter!(PARSE_FAILURES, "canteen" => canteen.get_identifier().to_string(), "kind" => kind)
+        .increment(1);
+}
+
+/// Record the dishes parsed from a menu page, which completes a successful scrape.
+pub(crate) fn record_dishes_parsed(canteen: Canteen, count: usize) {
+    counter!(DISHES_PARSED, "canteen" => canteen.get_identifier().to_string())
+        .increment(count as u64);
+    gauge!(LAST_SUCCESS, "canteen" => canteen.get_identifier().to_string())
+        .set(Utc::now().timestamp() as f64);
+}
+
+pub(crate) fn record_refresh(success: bool, duration: Duration) {
+    let result = if success { "success" } else { "error" };
+    counter!(REFRESHES, "result" => result).increment(1);
+    histogram!(REFRESH_DURATION).record(duration);
+}
+
+pub(crate) fn record_menu_update(stale_dishes: usize, new_dishes: usize) {
+    counter!(STALE_DISHES).increment(stale_dishes as u64);
+    counter!(NEW_DISHES).increment(new_dishes as u64);
+}
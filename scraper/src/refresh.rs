@@ -1,44 +1,58 @@
 use std::{
     collections::{BTreeSet, HashSet},
     str::FromStr,
-    sync::LazyLock,
+    time::Instant,
 };
 
 use chrono::{NaiveDate, Utc};
 use futures::{StreamExt, TryStreamExt as _};
 use itertools::Itertools;
-use shared::{Canteen, DishType};
+use shared::{Canteen, DishType, MENU_CHANGED_CHANNEL, MenuChanged, MenuChangedNotification};
 use sqlx::QueryBuilder;
-use strum::IntoEnumIterator as _;
 
 use crate::{
     Dish,
     dish::NutritionValues,
+    metrics,
     util::{self, add_menu_to_db, normalize_price_bigdecimal},
+    webhook,
 };
 
-static NON_FILTERED_CANTEENS: LazyLock<Vec<Canteen>> = LazyLock::new(|| {
-    let all_canteens = Canteen::iter().collect::<HashSet<_>>();
+/// Number of days, starting today, whose menus are already published and refreshed by the
+/// scraper.
+pub const UPCOMING_DAYS: i64 = 7;
 
-    all_canteens
-        .difference(&super::FILTER_CANTEENS)
-        .cloned()
-        .collect::<Vec<_>>()
-});
+/// Get the current date, in UTC. Which menus are refreshed and which count as current is decided
+/// by this date, so all such checks should use it.
+pub fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+/// Get the [`UPCOMING_DAYS`] days, starting [`today`].
+pub fn upcoming_days() -> impl Iterator<Item = NaiveDate> {
+    let today = today();
+    (0..UPCOMING_DAYS).map(move |days| today + chrono::Duration::days(days))
+}
 
+/// Refresh the menus of the given canteens at the given date if they were not scraped recently
+/// (or `force` is set), returning whether any menu was refreshed.
+///
+/// Menus that were never scraped are only refreshed for canteens that are not in
+/// `filter_canteens`, see [`config::Config::filter_canteens`](crate::config::Config::filter_canteens).
 #[tracing::instrument(skip(db))]
 pub async fn check_refresh(
     db: &sqlx::PgPool,
     date: NaiveDate,
     canteens: &[Canteen],
+    filter_canteens: &[Canteen],
     force: bool,
 ) -> bool {
-    if !force && date > Utc::now().date_naive() + chrono::Duration::days(31) {
+    if !force && date > today() + chrono::Duration::days(31) {
         tracing::debug!("Not refreshing menu for date {date} as it is too far in the future");
         return false;
     }
 
-    if !force && date < Utc::now().date_naive() {
+    if !force && date < today() {
         tracing::trace!("Not refreshing menu for date {date} as it is in the past");
         return false;
     }
@@ -60,7 +74,7 @@ pub async fn check_refresh(
             Ok(v) => v
                 .iter()
                 .map(|r| (Canteen::from_str(&r.canteen).expect("malformed db entry"), Some(r.scraped_at)))
-                .chain(NON_FILTERED_CANTEENS.iter().filter(|c| canteens.contains(c)).map(|c| (*c, None)))
+                .chain(canteens.iter().filter(|c| !filter_canteens.contains(c)).map(|c| (*c, None)))
                 .unique_by(|(c, _)| *c)
                 .filter(|(c, scraped_at)| 
                     canteens.contains(c) && scraped_at.is_none_or(|scraped_at| needs_refresh(scraped_at, date)))
@@ -82,17 +96,16 @@ pub async fn check_refresh(
             canteens_needing_refresh
         );
 
+        let start = Instant::now();
+
         let canteen_date_pairs = canteens_needing_refresh
             .iter()
             .map(|c| (date, *c))
             .collect::<Vec<_>>();
 
-        let scraped_dishes = util::scrape_canteens_at_days(&canteen_date_pairs)
+        let scraped_menus = util::scrape_canteens_at_days(&canteen_date_pairs)
             .filter_map(|res| async move { res.ok() })
-            .flat_map(|(_, canteen, menu)| {
-                futures::stream::iter(menu).map(move |dish| (canteen, dish))
-            })
-            .collect::<HashSet<_>>();
+            .collect::<Vec<_>>();
 
         let db_data = sqlx::query!(
             r#"SELECT canteen, name, image_src, price_students, price_employees, price_guests, vegetarian, vegan, dish_type AS "dish_type: DishType", kjoules, proteins, carbohydrates, fats FROM meals WHERE date = $1 AND is_latest = TRUE AND canteen = ANY($2)"#,
@@ -123,10 +136,38 @@ pub async fn check_refresh(
         )
         }).fetch(db).try_collect::<HashSet<_>>();
 
-        let (scraped_dishes, db_data) = futures::join!(scraped_dishes, db_data);
+        let (scraped_menus, db_data) = futures::join!(scraped_menus, db_data);
 
-        match db_data {
+        // the dishes of a canteen that failed to scrape would all look removed, so its menu is
+        // left untouched until the next refresh
+        let scraped_canteens = scraped_menus
+            .iter()
+            .map(|(_, canteen, _)| *canteen)
+            .collect::<Vec<_>>();
+        let failed_canteens = canteens_needing_refresh
+            .iter()
+            .filter(|canteen| !scraped_canteens.contains(canteen))
+            .collect::<Vec<_>>();
+        if !failed_canteens.is_empty() {
+            tracing::warn!(
+                "Not updating menu for date {} for canteens that failed to scrape: {:?}",
+                date,
+                failed_canteens
+            );
+        }
+
+        let scraped_dishes = scraped_menus
+            .into_iter()
+            .flat_map(|(_, canteen, menu)| menu.into_iter().map(move |dish| (canteen, dish)))
+            .collect::<HashSet<_>>();
+
+        let refreshed = match db_data {
+            Ok(_) if scraped_canteens.is_empty() => false,
             Ok(db_dishes) => {
+                let db_dishes = db_dishes
+                    .into_iter()
+                    .filter(|(canteen, _)| scraped_canteens.contains(canteen))
+                    .collect::<HashSet<_>>();
                 let stale_dishes = db_dishes
                     .difference(&scraped_dishes)
                     .collect::<HashSet<_>>();
@@ -135,7 +176,7 @@ pub async fn check_refresh(
                     .collect::<HashSet<_>>();
 
                 if let Err(err) =
-                    update_stale_dishes(db, date, &stale_dishes, &new_dishes, canteens).await
+                    update_stale_dishes(db, date, &stale_dishes, &new_dishes, &scraped_canteens).await
                 {
                     tracing::error!("Error updating stale dishes in db: {}", err);
                     false
@@ -147,16 +188,20 @@ pub async fn check_refresh(
                 tracing::error!("Error fetching existing dishes from db: {}", err);
                 false
             }
-        }
+        };
+
+        metrics::record_refresh(refreshed && failed_canteens.is_empty(), start.elapsed());
+
+        refreshed
     }
 }
 
 fn needs_refresh(last_refreshed: chrono::DateTime<Utc>, date_entry: chrono::NaiveDate) -> bool {
     let now = Utc::now();
 
-    if date_entry == now.naive_local().date() {
+    if date_entry == today() {
         now.signed_duration_since(last_refreshed) >= chrono::Duration::hours(8)
-    } else if date_entry < now.naive_local().date() {
+    } else if date_entry < today() {
         false
     } else {
         now.signed_duration_since(last_refreshed) >= chrono::Duration::days(2)
@@ -174,7 +219,8 @@ async fn update_stale_dishes(
     let mut tx = db.begin().await?;
 
     if !stale_dishes.is_empty() {
-        QueryBuilder::new("UPDATE meals SET is_latest = FALSE WHERE date = ")
+        // the replacing dishes are inserted in the same transaction, so they share its timestamp
+        QueryBuilder::new("UPDATE meals SET is_latest = FALSE, replaced_at = NOW() WHERE is_latest = TRUE AND date = ")
             .push_bind(date)
             .push(r#" AND ("name", canteen) IN "#)
             .push_tuples(stale_dishes, |mut sep, (canteen, dish)| {
@@ -212,7 +258,53 @@ async fn update_stale_dishes(
         add_menu_to_db(&mut tx, &date, canteen, menu).await?;
     }
 
+    // notifications are only delivered to listeners once the transaction commits
+    for notification in menu_changes(date, stale_dishes, new_dishes) {
+        webhook::enqueue_menu_change(&mut tx, &notification).await?;
+
+        let payload = serde_json::to_string(&MenuChangedNotification::from(&notification))?;
+        sqlx::query!("SELECT pg_notify($1, $2)", MENU_CHANGED_CHANNEL, payload)
+            .execute(&mut *tx)
+            .await?;
+    }
+
     tx.commit().await?;
 
+    metrics::record_menu_update(stale_dishes.len(), new_dishes.len());
+
     Ok(())
 }
+
+fn menu_changes(
+    date: NaiveDate,
+    stale_dishes: &HashSet<&(Canteen, Dish)>,
+    new_dishes: &HashSet<&(Canteen, Dish)>,
+) -> Vec<MenuChanged> {
+    let names_by_canteen = |dishes: &HashSet<&(Canteen, Dish)>, canteen: Canteen| {
+        dishes
+            .iter()
+            .filter(|(c, _)| *c == canteen)
+            .map(|(_, dish)| dish.name.clone())
+            .collect::<BTreeSet<_>>()
+    };
+
+    stale_dishes
+        .iter()
+        .chain(new_dishes.iter())
+        .map(|(canteen, _)| *canteen)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|canteen| {
+            let removed = names_by_canteen(stale_dishes, canteen);
+            let added = names_by_canteen(new_dishes, canteen);
+
+            MenuChanged {
+                canteen,
+                date,
+                added: added.difference(&removed).cloned().collect(),
+                removed: removed.difference(&added).cloned().collect(),
+                changed: added.intersection(&removed).cloned().collect(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig as _};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use tracing_subscriber::{EnvFilter, layer::SubscriberExt as _, util::SubscriberInitExt as _};
+
+/// Keeps the OTLP exporter alive and flushes the remaining spans when dropped.
+#[must_use = "spans are no longer exported once the guard is dropped"]
+pub struct TelemetryGuard {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take()
+            && let Err(err) = provider.shutdown()
+        {
+            eprintln!("Failed to shut down the trace exporter: {err}");
+        }
+    }
+}
+
+/// Install the global tracing subscriber, logging to stdout.
+///
+/// If an OTLP endpoint is given, spans are additionally exported to it via OTLP over HTTP and
+/// W3C trace context is used to propagate traces between services.
+pub fn init_tracing(
+    service_name: &'static str,
+    env_filter: EnvFilter,
+    otlp_endpoint: Option<&str>,
+) -> Result<TelemetryGuard> {
+    let provider = match otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+                .build()?;
+            Some(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_resource(Resource::builder().with_service_name(service_name).build())
+                    .build(),
+            )
+        }
+        None => None,
+    };
+
+    let otel_layer = provider.as_ref().map(|provider| {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(service_name))
+    });
+
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
+        .try_init()?;
+
+    Ok(TelemetryGuard { provider })
+}
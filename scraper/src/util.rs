@@ -1,16 +1,13 @@
-use std::env;
-
 use anyhow::Result;
 use chrono::NaiveDate;
 use futures::{Stream, StreamExt as _};
 use shared::{Canteen, DishType};
 use sqlx::{postgres::PgPoolOptions, types::Decimal, PgPool, PgTransaction};
 
-use crate::{scrape_menu, Dish};
+use crate::{config, scrape_menu, Dish};
 
 pub fn get_db() -> Result<PgPool> {
-    Ok(PgPoolOptions::new()
-        .connect_lazy(&env::var("DATABASE_URL").expect("missing DATABASE_URL env variable"))?)
+    Ok(PgPoolOptions::new().connect_lazy(&config::get().database_url)?)
 }
 
 pub fn scrape_canteens_at_days<'a>(
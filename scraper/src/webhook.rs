@@ -0,0 +1,515 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future;
+use hmac::{Hmac, Mac as _};
+use regex::RegexBuilder;
+use serde::Serialize;
+use sha2::Sha256;
+use shared::{Canteen, MenuChanged};
+use sqlx::{PgPool, PgTransaction, types::Uuid};
+
+/// Number of delivery attempts after which a delivery is given up.
+const MAX_ATTEMPTS: i32 = 6;
+/// Delay before the first retry, quadrupled with every further attempt.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Maximum number of deliveries claimed by a single call to [`deliver_pending`].
+const DELIVERY_BATCH_SIZE: i64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "webhook_delivery_status_enum")]
+#[sqlx(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub canteens: Vec<Canteen>,
+    pub dish_pattern: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct WebhookPayload<'a> {
+    event: &'static str,
+    #[serde(flatten)]
+    change: &'a MenuChanged,
+    /// Added dishes matching the dish pattern of the webhook.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matching_dishes: Option<Vec<&'a str>>,
+}
+
+/// Register a new webhook receiving changes of the menus of the given canteens (all canteens if
+/// empty), optionally only if an added dish matches the given case-insensitive regex.
+pub async fn add_webhook(
+    db: &PgPool,
+    url: &str,
+    secret: &str,
+    canteens: &[Canteen],
+    dish_pattern: Option<&str>,
+) -> Result<Uuid> {
+    let url = reqwest::Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("Webhook URL must use http or https");
+    }
+    if let Some(pattern) = dish_pattern {
+        RegexBuilder::new(pattern).case_insensitive(true).build()?;
+    }
+
+    let id = sqlx::query_scalar!(
+        "INSERT INTO webhooks (url, secret, canteens, dish_pattern) VALUES ($1, $2, $3, $4) RETURNING id",
+        url.as_str(),
+        secret,
+        &canteens
+            .iter()
+            .map(|c| c.get_identifier().to_string())
+            .collect::<Vec<_>>(),
+        dish_pattern,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(id)
+}
+
+/// Remove a webhook together with its delivery log, returning whether it existed.
+pub async fn remove_webhook(db: &PgPool, id: Uuid) -> Result<bool> {
+    let res = sqlx::query!("DELETE FROM webhooks WHERE id = $1", id)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn list_webhooks(db: &PgPool) -> Result<Vec<Webhook>> {
+    let webhooks = sqlx::query!(
+        "SELECT id, url, canteens, dish_pattern, created_at FROM webhooks ORDER BY created_at"
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|r| Webhook {
+        id: r.id,
+        url: r.url,
+        canteens: r
+            .canteens
+            .iter()
+            .map(|c| Canteen::from_str(c).expect("malformed db entry"))
+            .collect(),
+        dish_pattern: r.dish_pattern,
+        created_at: r.created_at,
+    })
+    .collect();
+
+    Ok(webhooks)
+}
+
+/// Get the most recent deliveries, optionally only of a single webhook.
+pub async fn list_deliveries(
+    db: &PgPool,
+    webhook_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<Delivery>> {
+    let deliveries = sqlx::query_as!(
+        Delivery,
+        r#"SELECT id, webhook_id, status AS "status: DeliveryStatus", attempts, created_at, delivered_at, last_status_code, last_error
+            FROM webhook_deliveries WHERE $1::UUID IS NULL OR webhook_id = $1
+            ORDER BY created_at DESC LIMIT $2"#,
+        webhook_id,
+        limit,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(deliveries)
+}
+
+/// Queue deliveries of a menu change for all interested webhooks.
+///
+/// Deliveries are stored in the same transaction as the menu change and sent afterwards by
+/// [`deliver_pending`].
+pub(crate) async fn enqueue_menu_change(
+    tx: &mut PgTransaction<'_>,
+    change: &MenuChanged,
+) -> Result<()> {
+    let webhooks = sqlx::query!(
+        "SELECT id, dish_pattern FROM webhooks WHERE cardinality(canteens) = 0 OR $1 = ANY(canteens)",
+        change.canteen.get_identifier()
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for webhook in webhooks {
+        let matching_dishes = match webhook.dish_pattern {
+            Some(pattern) => match RegexBuilder::new(&pattern).case_insensitive(true).build() {
+                Ok(regex) => {
+                    let matching = change
+                        .added
+                        .iter()
+                        .map(String::as_str)
+                        .filter(|name| regex.is_match(name))
+                        .collect::<Vec<_>>();
+                    if matching.is_empty() {
+                        continue;
+                    }
+                    Some(matching)
+                }
+                Err(err) => {
+                    tracing::warn!("Invalid dish pattern of webhook {}: {err}", webhook.id);
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let payload = serde_json::to_value(WebhookPayload {
+            event: "menu.changed",
+            change,
+            matching_dishes,
+        })?;
+
+        sqlx::query!(
+            "INSERT INTO webhook_deliveries (webhook_id, payload) VALUES ($1, $2)",
+            webhook.id,
+            payload
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Send all deliveries that are due, returning the number of successful deliveries.
+///
+/// Failed deliveries are retried with exponential backoff on later calls, until
+/// [`MAX_ATTEMPTS`] is reached.
+#[tracing::instrument(skip(db))]
+pub async fn deliver_pending(db: &PgPool) -> Result<usize> {
+    // claim the deliveries for a while, so concurrent workers do not send them as well
+    let deliveries = sqlx::query!(
+        r#"WITH claimed AS (
+                UPDATE webhook_deliveries SET next_attempt_at = NOW() + INTERVAL '5 minutes'
+                WHERE id IN (
+                    SELECT id FROM webhook_deliveries WHERE status = 'pending' AND next_attempt_at <= NOW()
+                    ORDER BY next_attempt_at LIMIT $1 FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, webhook_id, payload, attempts
+            )
+            SELECT claimed.id, claimed.webhook_id, claimed.payload, claimed.attempts, webhooks.url, webhooks.secret
+            FROM claimed JOIN webhooks ON webhooks.id = claimed.webhook_id"#,
+        DELIVERY_BATCH_SIZE
+    )
+    .fetch_all(db)
+    .await?;
+
+    if deliveries.is_empty() {
+        return Ok(0);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    let results = future::join_all(deliveries.into_iter().map(|delivery| {
+        let client = &client;
+        async move {
+            let body = serde_json::to_vec(&delivery.payload)?;
+            let res = send(client, &delivery.url, &delivery.secret, delivery.id, body).await;
+            record_attempt(db, delivery.id, delivery.attempts + 1, res).await
+        }
+    }))
+    .await;
+
+    let mut delivered = 0;
+    for res in results {
+        match res {
+            Ok(true) => delivered += 1,
+            Ok(false) => {}
+            Err(err) => tracing::error!("Failed to record webhook delivery: {err}"),
+        }
+    }
+
+    Ok(delivered)
+}
+
+/// Send a payload to a webhook, returning the status code of the response.
+async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    delivery_id: Uuid,
+    body: Vec<u8>,
+) -> Result<u16, (Option<u16>, String)> {
+    let timestamp = Utc::now().timestamp().to_string();
+
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Id", delivery_id.to_string())
+        .header("X-Webhook-Timestamp", &timestamp)
+        .header(
+            "X-Webhook-Signature",
+            format!("sha256={}", sign(secret, &timestamp, &body)),
+        )
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| (None, err.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((
+            Some(status.as_u16()),
+            format!("Receiver responded with {status}"),
+        ))
+    }
+}
+
+/// Compute the hex encoded HMAC-SHA256 of `{timestamp}.{body}`.
+fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+async fn record_attempt(
+    db: &PgPool,
+    delivery_id: Uuid,
+    attempts: i32,
+    res: Result<u16, (Option<u16>, String)>,
+) -> Result<bool> {
+    match res {
+        Ok(status_code) => {
+            sqlx::query!(
+                "UPDATE webhook_deliveries SET status = 'delivered', attempts = $2, delivered_at = NOW(), last_status_code = $3, last_error = NULL WHERE id = $1",
+                delivery_id,
+                attempts,
+                i32::from(status_code),
+            )
+            .execute(db)
+            .await?;
+
+            Ok(true)
+        }
+        Err((status_code, err)) => {
+            let status = if attempts >= MAX_ATTEMPTS {
+                tracing::warn!(
+                    "Giving up webhook delivery {delivery_id} after {attempts} attempts: {err}"
+                );
+                DeliveryStatus::Failed
+            } else {
+                tracing::debug!("Webhook delivery {delivery_id} failed: {err}");
+                DeliveryStatus::Pending
+            };
+            sqlx::query!(
+                "UPDATE webhook_deliveries SET status = $2, attempts = $3, next_attempt_at = NOW() + make_interval(secs => $4), last_status_code = $5, last_error = $6 WHERE id = $1",
+                delivery_id,
+                status as DeliveryStatus,
+                attempts,
+                retry_delay(attempts).as_secs_f64(),
+                status_code.map(i32::from),
+                err,
+            )
+            .execute(db)
+            .await?;
+
+            Ok(false)
+        }
+    }
+}
+
+/// Get the delay before the next attempt of a delivery that failed the given number of times.
+fn retry_delay(attempts: i32) -> Duration {
+    INITIAL_RETRY_DELAY * 4u32.pow(attempts.clamp(1, MAX_ATTEMPTS) as u32 - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::NaiveDate;
+    use tokio::{
+        io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    /// A request received by [`Receiver`].
+    struct ReceivedRequest {
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    /// Local HTTP server answering every request with `500 Internal Server Error`.
+    struct Receiver {
+        url: String,
+        requests: mpsc::UnboundedReceiver<ReceivedRequest>,
+    }
+
+    impl Receiver {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let (sender, requests) = mpsc::unbounded_channel();
+
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let mut stream = BufReader::new(stream);
+
+                    let mut headers = HashMap::new();
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    loop {
+                        line.clear();
+                        stream.read_line(&mut line).await.unwrap();
+                        match line.trim_end().split_once(':') {
+                            Some((name, value)) => {
+                                headers.insert(name.to_lowercase(), value.trim().to_string())
+                            }
+                            None => break,
+                        };
+                    }
+
+                    let length = headers["content-length"].parse().unwrap();
+                    let mut body = vec![0; length];
+                    stream.read_exact(&mut body).await.unwrap();
+
+                    stream
+                        .write_all(b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                        .await
+                        .unwrap();
+                    sender.send(ReceivedRequest { headers, body }).ok();
+                }
+            });
+
+            Self { url, requests }
+        }
+    }
+
+    #[test]
+    fn retry_delay_is_quadrupled_per_attempt() {
+        let delays = (1..=MAX_ATTEMPTS)
+            .map(|attempts| retry_delay(attempts).as_secs())
+            .collect::<Vec<_>>();
+
+        assert_eq!(delays, [30, 120, 480, 1920, 7680, 30720]);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL)"]
+    async fn failing_delivery_is_signed_retried_and_given_up(db: PgPool) -> Result<()> {
+        let mut receiver = Receiver::start().await;
+        let webhook_id = add_webhook(&db, &receiver.url, SECRET, &[], None).await?;
+
+        let change = MenuChanged {
+            canteen: Canteen::Forum,
+            date: NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(),
+            added: vec!["Bratwurst mit Currysauce und Pommes Frites".to_string()],
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        let mut tx = db.begin().await?;
+        enqueue_menu_change(&mut tx, &change).await?;
+        tx.commit().await?;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let attempted_at = sqlx::query_scalar!(r#"SELECT NOW() AS "now!""#)
+                .fetch_one(&db)
+                .await?;
+            assert_eq!(deliver_pending(&db).await?, 0);
+
+            let request = receiver.requests.recv().await.unwrap();
+            let timestamp = &request.headers["x-webhook-timestamp"];
+            let signature = request.headers["x-webhook-signature"]
+                .strip_prefix("sha256=")
+                .unwrap();
+            let signature = (0..signature.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&signature[i..i + 2], 16).unwrap())
+                .collect::<Vec<_>>();
+            let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+            mac.update(format!("{timestamp}.").as_bytes());
+            mac.update(&request.body);
+            mac.verify_slice(&signature)
+                .expect("signature does not match the body");
+
+            let delivery = list_deliveries(&db, Some(webhook_id), 10).await?;
+            let [delivery] = delivery.as_slice() else {
+                panic!("expected a single delivery, got {delivery:?}");
+            };
+            assert_eq!(delivery.attempts, attempt);
+            assert_eq!(delivery.last_status_code, Some(500));
+            assert!(delivery.last_error.is_some());
+            assert_eq!(delivery.delivered_at, None);
+
+            if attempt < MAX_ATTEMPTS {
+                assert_eq!(delivery.status, DeliveryStatus::Pending);
+
+                let next_attempt_at = sqlx::query_scalar!(
+                    "SELECT next_attempt_at FROM webhook_deliveries WHERE id = $1",
+                    delivery.id
+                )
+                .fetch_one(&db)
+                .await?;
+                let delay = (next_attempt_at - attempted_at).to_std()?;
+                let expected = retry_delay(attempt);
+                assert!(
+                    delay >= expected && delay < expected + Duration::from_secs(5),
+                    "attempt {attempt} is retried after {delay:?} instead of {expected:?}"
+                );
+
+                // skip the backoff
+                sqlx::query!(
+                    "UPDATE webhook_deliveries SET next_attempt_at = NOW() WHERE id = $1",
+                    delivery.id
+                )
+                .execute(&db)
+                .await?;
+            } else {
+                assert_eq!(delivery.status, DeliveryStatus::Failed);
+            }
+        }
+
+        // given up deliveries are not sent again
+        sqlx::query!("UPDATE webhook_deliveries SET next_attempt_at = NOW()")
+            .execute(&db)
+            .await?;
+        assert_eq!(deliver_pending(&db).await?, 0);
+        assert!(receiver.requests.try_recv().is_err());
+
+        Ok(())
+    }
+}
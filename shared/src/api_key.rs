@@ -0,0 +1,13 @@
+use strum::{Display, EnumIter, EnumString};
+
+/// Quota tier of an API key, determining how many requests can be made with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, EnumString, Display, sqlx::Type)]
+#[sqlx(type_name = "api_key_tier_enum")]
+#[sqlx(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ApiKeyTier {
+    Basic,
+    Extended,
+    /// Requests are not rate limited at all.
+    Unlimited,
+}
@@ -1,7 +1,11 @@
 use std::fmt::Display;
 
+mod api_key;
 mod canteen;
+mod notification;
+pub use api_key::ApiKeyTier;
 pub use canteen::Canteen;
+pub use notification::{MENU_CHANGED_CHANNEL, MenuChanged, MenuChangedNotification};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type)]
 #[sqlx(type_name = "dish_type_enum")]
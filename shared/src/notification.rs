@@ -0,0 +1,40 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::Canteen;
+
+/// Postgres channel on which a [`MenuChangedNotification`] is sent whenever the stored menu of a
+/// canteen changes.
+pub const MENU_CHANGED_CHANNEL: &str = "menu_changed";
+
+/// Changes to the stored menu of a canteen at a date.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MenuChanged {
+    pub canteen: Canteen,
+    pub date: NaiveDate,
+    /// Names of dishes that were not on the menu before.
+    pub added: Vec<String>,
+    /// Names of dishes that are no longer on the menu.
+    pub removed: Vec<String>,
+    /// Names of dishes that are still on the menu, but with different details (e.g. prices).
+    pub changed: Vec<String>,
+}
+
+/// Payload of a notification on the [`MENU_CHANGED_CHANNEL`].
+///
+/// Postgres limits notification payloads to 8000 bytes, so only the changed menu is identified
+/// and listeners query its contents themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MenuChangedNotification {
+    pub canteen: Canteen,
+    pub date: NaiveDate,
+}
+
+impl From<&MenuChanged> for MenuChangedNotification {
+    fn from(changed: &MenuChanged) -> Self {
+        Self {
+            canteen: changed.canteen,
+            date: changed.date,
+        }
+    }
+}
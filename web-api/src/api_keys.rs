@@ -0,0 +1,86 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use actix_web::http::header::{self, HeaderMap};
+use mensa_upb_scraper::api_key::hash_api_key;
+use shared::ApiKeyTier;
+use sqlx::{types::Uuid, PgPool};
+
+/// Interval in which the API keys are reloaded from the database, e.g. to pick up revocations.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub tier: ApiKeyTier,
+}
+
+/// The request carries an API key that does not exist or was revoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidApiKey;
+
+/// In-memory copy of the active API keys, indexed by their hash.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyStore {
+    keys: Arc<RwLock<HashMap<String, ApiKey>>>,
+}
+
+impl ApiKeyStore {
+    pub async fn reload(&self, db: &PgPool) -> sqlx::Result<()> {
+        let keys = sqlx::query!(
+            r#"SELECT id, key_hash, tier AS "tier: ApiKeyTier" FROM api_keys WHERE revoked_at IS NULL"#
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|r| {
+            (
+                r.key_hash,
+                ApiKey {
+                    id: r.id,
+                    tier: r.tier,
+                },
+            )
+        })
+        .collect();
+
+        *self.keys.write().expect("api key lock poisoned") = keys;
+
+        Ok(())
+    }
+
+    /// Get the API key passed as bearer token in the `Authorization` header, or `None` for
+    /// anonymous requests.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Option<ApiKey>, InvalidApiKey> {
+        let Some(authorization) = headers.get(header::AUTHORIZATION) else {
+            return Ok(None);
+        };
+        let token = authorization
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or(InvalidApiKey)?;
+
+        self.keys
+            .read()
+            .expect("api key lock poisoned")
+            .get(&hash_api_key(token))
+            .copied()
+            .map(Some)
+            .ok_or(InvalidApiKey)
+    }
+}
+
+/// Periodically reload the API keys from the database.
+pub async fn reload_api_keys(db: PgPool, store: ApiKeyStore) {
+    loop {
+        tokio::time::sleep(RELOAD_INTERVAL).await;
+        if let Err(err) = store.reload(&db).await {
+            tracing::error!("Failed to load API keys: {err}");
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::http::header::{self, HeaderMap};
+use ipnet::{IpNet, Ipv6Net};
+
+use mensa_upb_scraper::config;
+
+/// Get the address of the client that sent a request.
+///
+/// The `Forwarded` (or, if absent, `X-Forwarded-For`) header is only used if the request comes
+/// from a trusted proxy. Its chain of addresses is walked from the right, skipping all trusted
+/// proxies, so clients cannot spoof their address by sending the header themselves.
+pub fn client_ip(peer_addr: IpAddr, headers: &HeaderMap) -> IpAddr {
+    client_ip_behind(peer_addr, headers, &config::get().api.trusted_proxies)
+}
+
+fn client_ip_behind(peer_addr: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let is_trusted_proxy = |ip: IpAddr| trusted_proxies.iter().any(|net| net.contains(&ip));
+    let mut client = peer_addr.to_canonical();
+
+    if is_trusted_proxy(client) {
+        for hop in forwarded_chain(headers).into_iter().rev() {
+            // stop at hops we cannot identify, the last known proxy is the best guess left
+            let Some(hop) = hop else {
+                break;
+            };
+            client = hop.to_canonical();
+            if !is_trusted_proxy(client) {
+                break;
+            }
+        }
+    }
+
+    client
+}
+
+/// Get the address rate limits are applied to for a client, which is the prefix of length
+/// `api.rate_limit.ipv6_prefix` for IPv6, as customers often get a whole prefix instead of a single
+/// address.
+pub fn rate_limit_address(ip: IpAddr) -> IpAddr {
+    prefix_address(ip, config::get().api.rate_limit.ipv6_prefix)
+}
+
+fn prefix_address(ip: IpAddr, ipv6_prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ipv6) => Ipv6Net::new(ipv6, ipv6_prefix)
+            .map(|net| IpAddr::V6(net.network()))
+            .unwrap_or(ip),
+    }
+}
+
+/// Whether requests of a client are never rate limited.
+pub fn is_rate_limit_exempt(ip: IpAddr) -> bool {
+    config::get()
+        .api
+        .rate_limit
+        .exempt
+        .iter()
+        .any(|net| net.contains(&ip))
+}
+
+/// Get the chain of forwarded client addresses, from the original client to the last proxy.
+///
+/// Entries that are not an IP address (e.g. `unknown` or obfuscated identifiers) are `None`.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    if headers.contains_key(header::FORWARDED) {
+        header_values(headers, header::FORWARDED)
+            .flat_map(|value| value.split(','))
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, value)| parse_node(value.trim().trim_matches('"')))
+            })
+            .collect()
+    } else {
+        header_values(headers, header::X_FORWARDED_FOR)
+            .flat_map(|value| value.split(','))
+            .map(|node| parse_node(node.trim()))
+            .collect()
+    }
+}
+
+fn header_values(
+    headers: &HeaderMap,
+    name: header::HeaderName,
+) -> impl Iterator<Item = &str> + use<'_> {
+    headers
+        .get_all(name)
+        .filter_map(|value| value.to_str().ok())
+}
+
+/// Parse a node of a forwarding chain, which is an IP address optionally followed by a port, with
+/// IPv6 addresses possibly enclosed in brackets.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+        .or_else(|| {
+            node.strip_prefix('[')
+                .and_then(|node| node.strip_suffix(']'))
+                .and_then(|node| node.parse().ok())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(entries: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.append(name.clone(), HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    }
+
+    #[test]
+    fn headers_of_untrusted_peers_are_ignored() {
+        let headers = headers(&[(header::X_FORWARDED_FOR, "198.51.100.7")]);
+
+        assert_eq!(
+            client_ip_behind(ip("203.0.113.1"), &headers, &trusted()),
+            ip("203.0.113.1")
+        );
+    }
+
+    #[test]
+    fn spoofed_left_most_entry_is_ignored() {
+        // the client sent "198.51.100.7" itself, the trusted proxy appended the real address
+        let headers = headers(&[(header::X_FORWARDED_FOR, "198.51.100.7, 203.0.113.1")]);
+
+        assert_eq!(
+            client_ip_behind(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.1")
+        );
+    }
+
+    #[test]
+    fn trusted_hops_are_skipped_up_to_the_first_untrusted_one() {
+        let headers = headers(&[
+            (
+                header::X_FORWARDED_FOR,
+                "198.51.100.7, 203.0.113.1, 10.0.0.2",
+            ),
+            (header::X_FORWARDED_FOR, "10.0.0.3"),
+        ]);
+
+        assert_eq!(
+            client_ip_behind(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.1")
+        );
+    }
+
+    #[test]
+    fn chain_of_trusted_hops_ends_at_the_first_entry() {
+        let headers = headers(&[(header::X_FORWARDED_FOR, "10.0.0.3, 10.0.0.2")]);
+
+        assert_eq!(
+            client_ip_behind(ip("10.0.0.1"), &headers, &trusted()),
+            ip("10.0.0.3")
+        );
+    }
+
+    #[test]
+    fn forwarded_header_takes_precedence() {
+        let headers = headers(&[
+            (
+                header::FORWARDED,
+                r#"for=198.51.100.7, for="[2001:db8::1]:4711";proto=https"#,
+            ),
+            (header::X_FORWARDED_FOR, "203.0.113.1"),
+        ]);
+
+        assert_eq!(
+            client_ip_behind(ip("10.0.0.1"), &headers, &trusted()),
+            ip("2001:db8::1")
+        );
+    }
+
+    #[test]
+    fn forwarded_nodes_are_parsed() {
+        let headers = headers(&[(
+            header::FORWARDED,
+            r#"for=198.51.100.7:80, For="[2001:db8::1]", for="198.51.100.8", by=10.0.0.1;for=unknown, proto=http"#,
+        )]);
+
+        assert_eq!(
+            forwarded_chain(&headers),
+            vec![
+                Some(ip("198.51.100.7")),
+                Some(ip("2001:db8::1")),
+                Some(ip("198.51.100.8")),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_hops_end_the_chain() {
+        let malformed = headers(&[(header::X_FORWARDED_FOR, "203.0.113.1, not-an-ip, 10.0.0.2")]);
+
+        assert_eq!(
+            client_ip_behind(ip("10.0.0.1"), &malformed, &trusted()),
+            ip("10.0.0.2")
+        );
+
+        let obfuscated = headers(&[(header::FORWARDED, "for=_hidden")]);
+        assert_eq!(
+            client_ip_behind(ip("10.0.0.1"), &obfuscated, &trusted()),
+            ip("10.0.0.1")
+        );
+
+        // header values that are not visible ASCII are skipped entirely
+        let mut invalid = headers(&[(header::X_FORWARDED_FOR, "203.0.113.1")]);
+        invalid.append(
+            header::X_FORWARDED_FOR,
+            HeaderValue::from_bytes(b"198.51.100.7\xff").unwrap(),
+        );
+        assert_eq!(
+            client_ip_behind(ip("10.0.0.1"), &invalid, &trusted()),
+            ip("203.0.113.1")
+        );
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_are_canonicalized() {
+        let headers = headers(&[(header::X_FORWARDED_FOR, "::ffff:203.0.113.1")]);
+
+        assert_eq!(
+            client_ip_behind(ip("::ffff:10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.1")
+        );
+    }
+
+    #[test]
+    fn ipv6_addresses_are_grouped_by_prefix() {
+        assert_eq!(
+            prefix_address(ip("2001:db8:1:2:3:4:5:6"), 64),
+            ip("2001:db8:1:2::")
+        );
+        assert_eq!(
+            prefix_address(ip("2001:db8:1:2ff:3:4:5:6"), 56),
+            ip("2001:db8:1:200::")
+        );
+        // the last address of a prefix still belongs to it, the next one does not
+        assert_eq!(
+            prefix_address(ip("2001:db8:1:2:ffff:ffff:ffff:ffff"), 64),
+            ip("2001:db8:1:2::")
+        );
+        assert_eq!(
+            prefix_address(ip("2001:db8:1:3::"), 64),
+            ip("2001:db8:1:3::")
+        );
+        assert_eq!(prefix_address(ip("2001:db8::1"), 128), ip("2001:db8::1"));
+        assert_eq!(prefix_address(ip("203.0.113.1"), 64), ip("203.0.113.1"));
+    }
+}
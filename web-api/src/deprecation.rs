@@ -0,0 +1,66 @@
+use std::{sync::LazyLock, time::SystemTime};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue, HttpDate, LINK},
+    middleware::Next,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// When the unversioned routes were deprecated in favor of `/v1`.
+static DEPRECATED_AT: LazyLock<DateTime<Utc>> = LazyLock::new(|| {
+    NaiveDate::from_ymd_opt(2026, 10, 19)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .expect("valid date")
+        .and_utc()
+});
+/// When the unversioned routes will be removed.
+static SUNSET_AT: LazyLock<DateTime<Utc>> = LazyLock::new(|| {
+    NaiveDate::from_ymd_opt(2027, 4, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .expect("valid date")
+        .and_utc()
+});
+
+/// Middleware marking responses of the unversioned routes as deprecated.
+///
+/// Adds the `Deprecation` (RFC 9745) and `Sunset` (RFC 8594) headers and links to the same route
+/// under `/v1` as the successor.
+pub(crate) async fn deprecate_unversioned(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let path = format!("/v1{}", req.path().trim_end_matches('/'));
+    let successor = match req.query_string() {
+        "" => format!("<{path}>; rel=\"successor-version\""),
+        query => format!("<{path}?{query}>; rel=\"successor-version\""),
+    };
+
+    let mut res = next.call(req).await?;
+
+    // requests to paths without a route are not deprecated, they never existed
+    if res.request().match_pattern().is_none() {
+        return Ok(res);
+    }
+
+    let headers = res.headers_mut();
+    headers.insert(
+        DEPRECATION,
+        HeaderValue::from_str(&format!("@{}", DEPRECATED_AT.timestamp()))?,
+    );
+    headers.insert(
+        SUNSET,
+        HttpDate::from(SystemTime::from(*SUNSET_AT))
+            .to_string()
+            .parse()?,
+    );
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.append(LINK, link);
+    }
+
+    Ok(res)
+}
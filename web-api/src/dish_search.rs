@@ -0,0 +1,237 @@
+use std::str::FromStr as _;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use shared::Canteen;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+/// Maximum number of "did you mean" suggestions for unknown dish names.
+const MAX_SUGGESTIONS: i64 = 3;
+/// Names shorter than this (in characters) share too few trigrams with dish names to suggest any.
+const MIN_SUGGESTION_NAME_LENGTH: usize = 3;
+/// Names longer than this (in characters) are not dish names, and are expensive to compare.
+const MAX_SUGGESTION_NAME_LENGTH: usize = 200;
+
+/// A dish whose name is similar to a search query.
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct DishMatch {
+    /// Stable identifier of the dish, see `/dishes/{id}`.
+    pub id: Uuid,
+    pub name: String,
+    /// The canteens the dish was served at.
+    pub canteens: Vec<Canteen>,
+    /// Date the dish was last served at any of the canteens.
+    pub last_served: NaiveDate,
+    /// Similarity of the name to the query, between 0 and 1.
+    pub score: f32,
+}
+
+/// A dish name completing a prefix typed by a user.
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct DishSuggestion {
+    /// Stable identifier of the dish, see `/dishes/{id}`.
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Parameters of a search for dishes by name.
+#[derive(Debug, Clone, Copy)]
+pub struct DishSearch<'a> {
+    pub query: &'a str,
+    /// Only consider dishes served at these canteens.
+    pub canteens: Option<&'a [Canteen]>,
+    /// Only consider dishes that are (not) vegan.
+    pub vegan: Option<bool>,
+    pub limit: i64,
+}
+
+/// Search dishes by the trigram similarity of their names to the query, best matches first.
+///
+/// The query is compared to the most similar part of each name, so that searching for a single
+/// word finds all dishes containing it.
+pub async fn search_dishes(db: &PgPool, search: DishSearch<'_>) -> sqlx::Result<Vec<DishMatch>> {
+    let canteens = search.canteens.map(|canteens| {
+        canteens
+            .iter()
+            .map(|c| c.get_identifier().to_string())
+            .collect::<Vec<_>>()
+    });
+
+    let rows = sqlx::query!(
+        r#"SELECT d.id, d.name, array_agg(DISTINCT m.canteen ORDER BY m.canteen) AS "canteens!", MAX(m.date) AS "last_served!", MAX(word_similarity($1, m.name)) AS "score!"
+            FROM meals m JOIN dishes d ON d.id = m.dish_id
+            WHERE m.is_latest = TRUE AND $1 <% m.name AND ($2::text[] IS NULL OR m.canteen = ANY($2)) AND ($3::bool IS NULL OR m.vegan = $3)
+            GROUP BY d.id, d.name
+            ORDER BY "score!" DESC, "last_served!" DESC, d.name
+            LIMIT $4"#,
+        search.query,
+        canteens.as_deref(),
+        search.vegan,
+        search.limit,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DishMatch {
+            id: row.id,
+            name: row.name,
+            canteens: row
+                .canteens
+                .iter()
+                .map(|canteen| Canteen::from_str(canteen).expect("Invalid database entry"))
+                .collect(),
+            last_served: row.last_served,
+            score: row.score,
+        })
+        .collect())
+}
+
+/// Get the dishes whose names start with the given prefix, ignoring casing, punctuation and
+/// whitespace.
+///
+/// Dishes are ranked by how often they were served, with every serving counting `1/e` as much
+/// as one served 90 days later, so that both staples and the dishes of the current week come
+/// first.
+pub async fn suggest_dishes(
+    db: &PgPool,
+    prefix: &str,
+    limit: i64,
+) -> sqlx::Result<Vec<DishSuggestion>> {
+    // normalized like the stored names by the database, as it also handles non-ASCII characters
+    let prefix = sqlx::query_scalar!(r#"SELECT normalize_dish_name($1) AS "prefix!""#, prefix)
+        .fetch_one(db)
+        .await?;
+    // only names consisting of the last code point have no upper bound, no dish is named like that
+    let Some(upper_bound) = prefix_upper_bound(&prefix) else {
+        return Ok(Vec::new());
+    };
+
+    // a range instead of `LIKE` lets generic plans of the prepared statement use the index, too
+    sqlx::query_as!(
+        DishSuggestion,
+        r#"SELECT d.id, d.name
+            FROM dishes d JOIN meals m ON m.dish_id = d.id
+            WHERE d.normalized_name ~>=~ $1 AND d.normalized_name ~<~ $2 AND m.is_latest = TRUE
+            GROUP BY d.id, d.name
+            ORDER BY SUM(exp(GREATEST(m.date - CURRENT_DATE, -36500) / 90.0::float8)) DESC, d.name
+            LIMIT $3"#,
+        prefix,
+        upper_bound,
+        limit,
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Get the smallest string greater than all strings starting with the prefix, if there is one.
+///
+/// UTF-8 preserves the order of code points, so this holds for the bytewise comparison of
+/// `text_pattern_ops` as well.
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars = prefix.chars().collect::<Vec<_>>();
+    while let Some(last) = chars.pop() {
+        // skips the surrogates, which are no characters
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// Get the names of the dishes most similar to an unknown dish name, or none if the name is too
+/// short or too long to be compared.
+pub async fn did_you_mean(db: &PgPool, name: &str) -> sqlx::Result<Vec<String>> {
+    let length = name.trim().chars().count();
+    if !(MIN_SUGGESTION_NAME_LENGTH..=MAX_SUGGESTION_NAME_LENGTH).contains(&length) {
+        return Ok(Vec::new());
+    }
+
+    let matches = search_dishes(
+        db,
+        DishSearch {
+            query: name,
+            canteens: None,
+            vegan: None,
+            limit: MAX_SUGGESTIONS,
+        },
+    )
+    .await?;
+
+    Ok(matches.into_iter().map(|dish| dish.name).collect())
+}
+
+/// Normalize a dish name like `normalize_dish_name` in the database, for the ASCII characters it
+/// contains: runs of punctuation and whitespace are collapsed into a single space, letters are
+/// lowercased and the name is trimmed.
+///
+/// Other characters are kept as they are, so names normalized equally are always normalized
+/// equally by the database as well.
+pub fn normalize_dish_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut separated = false;
+    for c in name.chars() {
+        // POSIX `[:space:]` also contains the vertical tab, unlike `char::is_ascii_whitespace`
+        if c.is_ascii_punctuation() || c.is_ascii_whitespace() || c == '\x0b' {
+            separated = true;
+        } else {
+            if separated && !normalized.is_empty() {
+                normalized.push(' ');
+            }
+            separated = false;
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+/// Get the identifier of the dish with the given name, ignoring casing, punctuation and
+/// whitespace, if the dish is known.
+pub async fn find_dish_id(db: &PgPool, name: &str) -> sqlx::Result<Option<Uuid>> {
+    sqlx::query_scalar!(
+        "SELECT id FROM dishes WHERE normalized_name = normalize_dish_name($1)",
+        name
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Get the identifier of the dish with the given name, ignoring casing, punctuation and
+/// whitespace.
+///
+/// Fails with suggestions of similar dishes if no dish has that name.
+pub async fn resolve_dish_name(db: &PgPool, name: &str) -> Result<Uuid, ApiError> {
+    match find_dish_id(db, name).await? {
+        Some(id) => Ok(id),
+        None => Err(ApiError::DishNotFound(did_you_mean(db, name).await?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_upper_bound_increments_the_last_character() {
+        assert_eq!(prefix_upper_bound("pasta"), Some("pastb".to_string()));
+        assert_eq!(prefix_upper_bound("käse"), Some("käsf".to_string()));
+        assert_eq!(prefix_upper_bound("k\u{d7ff}"), Some("k\u{e000}".to_string()));
+        assert_eq!(prefix_upper_bound("k\u{10ffff}"), Some("l".to_string()));
+        assert_eq!(prefix_upper_bound("\u{10ffff}"), None);
+    }
+
+    #[test]
+    fn prefix_upper_bound_is_greater_than_all_names_with_the_prefix() {
+        let bound = prefix_upper_bound("pasta").unwrap();
+
+        for name in ["pasta", "pasta al forno", "pasta\u{10ffff}"] {
+            assert!(name.as_bytes() < bound.as_bytes());
+        }
+        assert!("pastb".as_bytes() >= bound.as_bytes());
+    }
+}
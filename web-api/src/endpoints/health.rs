@@ -0,0 +1,253 @@
+use std::{collections::HashMap, str::FromStr as _, time::Duration};
+
+use actix_web::{get, web, HttpResponse, Responder};
+use chrono::{DateTime, TimeDelta, Utc};
+use mensa_upb_scraper::{config, today};
+use serde::Serialize;
+use shared::Canteen;
+use sqlx::{migrate::Migrate as _, PgPool};
+use strum::IntoEnumIterator as _;
+use utoipa_actix_web::service_config::ServiceConfig;
+
+use crate::MIGRATOR;
+
+/// Time after which a database check counts as failed.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        utoipa_actix_web::scope("/health")
+            .service(live)
+            .service(ready),
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum HealthStatus {
+    Ok,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    fn from_healthy(healthy: bool) -> Self {
+        if healthy {
+            Self::Ok
+        } else {
+            Self::Unhealthy
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct LiveResponse {
+    status: HealthStatus,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct ReadyResponse {
+    /// `ok` if all checks passed.
+    status: HealthStatus,
+    database: DatabaseHealth,
+    migrations: MigrationsHealth,
+    /// Freshness of the menu of the current day per canteen.
+    canteens: Vec<CanteenHealth>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct DatabaseHealth {
+    status: HealthStatus,
+    /// Time it took to answer a query, in milliseconds.
+    latency_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct MigrationsHealth {
+    status: HealthStatus,
+    /// Migrations known to the API that were not applied to the database yet.
+    pending: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct CanteenHealth {
+    canteen: String,
+    status: HealthStatus,
+    /// When the menu of the current day was last scraped, if ever.
+    last_scraped_at: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(summary = "Liveness", description = "Check whether the API is running.", responses(
+    (status = OK, description = "The API is running.", body = LiveResponse),
+))]
+#[get("/live")]
+async fn live() -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(LiveResponse {
+            status: HealthStatus::Ok,
+        })
+}
+
+#[utoipa::path(summary = "Readiness", description = "Check whether the API can serve requests: the database is reachable, all migrations are applied and the menus of the current day are up to date.", responses(
+    (status = OK, description = "All checks passed.", body = ReadyResponse),
+    (status = SERVICE_UNAVAILABLE, description = "At least one check failed.", body = ReadyResponse),
+))]
+#[get("/ready")]
+async fn ready(db: web::Data<PgPool>) -> impl Responder {
+    let database = check_database(&db).await;
+    let (migrations, canteens) = if database.status == HealthStatus::Ok {
+        tokio::join!(check_migrations(&db), check_canteens(&db))
+    } else {
+        (
+            MigrationsHealth {
+                status: HealthStatus::Unhealthy,
+                pending: Vec::new(),
+                error: Some("Database unavailable".to_string()),
+            },
+            Vec::new(),
+        )
+    };
+
+    let healthy = database.status == HealthStatus::Ok
+        && migrations.status == HealthStatus::Ok
+        && canteens.iter().all(|c| c.status == HealthStatus::Ok);
+    let response = ReadyResponse {
+        status: HealthStatus::from_healthy(healthy),
+        database,
+        migrations,
+        canteens,
+    };
+
+    if healthy {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    }
+    .insert_header(("Cache-Control", "no-store"))
+    .json(response)
+}
+
+async fn check_database(db: &PgPool) -> DatabaseHealth {
+    let start = std::time::Instant::now();
+    let res = tokio::time::timeout(
+        DATABASE_TIMEOUT,
+        sqlx::query!("SELECT 1 AS one").fetch_one(db),
+    )
+    .await;
+
+    let error = match res {
+        Ok(Ok(_)) => None,
+        Ok(Err(err)) => Some(err.to_string()),
+        Err(_) => Some("Timed out".to_string()),
+    };
+    if let Some(err) = &error {
+        tracing::warn!("Database health check failed: {err}");
+    }
+
+    DatabaseHealth {
+        status: HealthStatus::from_healthy(error.is_none()),
+        latency_ms: error.is_none().then(|| start.elapsed().as_millis()),
+        error,
+    }
+}
+
+async fn check_migrations(db: &PgPool) -> MigrationsHealth {
+    let applied = match db.acquire().await {
+        Ok(mut conn) => conn
+            .list_applied_migrations()
+            .await
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
+    match applied {
+        Ok(applied) => {
+            let pending = MIGRATOR
+                .iter()
+                .filter(|m| m.migration_type.is_up_migration())
+                .filter(|m| !applied.iter().any(|a| a.version == m.version))
+                .map(|m| format!("{}_{}", m.version, m.description))
+                .collect::<Vec<_>>();
+
+            MigrationsHealth {
+                status: HealthStatus::from_healthy(pending.is_empty()),
+                pending,
+                error: None,
+            }
+        }
+        Err(err) => MigrationsHealth {
+            status: HealthStatus::Unhealthy,
+            pending: Vec::new(),
+            error: Some(err),
+        },
+    }
+}
+
+async fn check_canteens(db: &PgPool) -> Vec<CanteenHealth> {
+    let today = today();
+    let max_age = TimeDelta::hours(config::get().api.health_max_menu_age_hours.into());
+
+    let last_scraped = match sqlx::query!(
+        r#"SELECT canteen, max(scraped_at) AS "scraped_at!" FROM canteens_scraped WHERE scraped_for = $1 GROUP BY canteen"#,
+        today
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(rows) => rows
+            .into_iter()
+            .filter_map(|r| Some((Canteen::from_str(&r.canteen).ok()?, r.scraped_at)))
+            .collect::<HashMap<_, _>>(),
+        Err(err) => {
+            tracing::error!("Failed to query database: {err}");
+            HashMap::new()
+        }
+    };
+
+    Canteen::iter()
+        .filter(|c| !config::get().filter_canteens.contains(c))
+        .map(|canteen| {
+            let last_scraped_at = last_scraped.get(&canteen).copied();
+            CanteenHealth {
+                canteen: canteen.get_identifier().to_string(),
+                status: HealthStatus::from_healthy(is_fresh(last_scraped_at, Utc::now(), max_age)),
+                last_scraped_at,
+            }
+        })
+        .collect()
+}
+
+/// Whether the menu of a canteen last scraped at `last_scraped_at` counts as current at `now`.
+fn is_fresh(
+    last_scraped_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    max_age: TimeDelta,
+) -> bool {
+    last_scraped_at.is_some_and(|at| now - at <= max_age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menus_count_as_fresh_up_to_the_max_age() {
+        let now = DateTime::parse_from_rfc3339("2026-10-19T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let max_age = TimeDelta::hours(26);
+
+        assert!(is_fresh(Some(now), now, max_age));
+        assert!(is_fresh(Some(now - max_age), now, max_age));
+        assert!(!is_fresh(
+            Some(now - max_age - TimeDelta::seconds(1)),
+            now,
+            max_age
+        ));
+        assert!(!is_fresh(None, now, max_age));
+    }
+}
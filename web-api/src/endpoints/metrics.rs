@@ -0,0 +1,56 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use mensa_upb_scraper::config;
+use metrics_exporter_prometheus::PrometheusHandle;
+use sqlx::PgPool;
+use utoipa_actix_web::service_config::ServiceConfig;
+
+use crate::{
+    client_ip,
+    error::{ApiError, Problem},
+    metrics, MenuCache,
+};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(prometheus_metrics);
+}
+
+/// Only allow clients listed in `api.metrics_allowed` (e.g. monitoring) to read metrics, which are
+/// not meant to be public. Other clients get the same response as for an unknown route.
+fn ensure_allowed(req: &HttpRequest) -> Result<(), ApiError> {
+    let peer_addr = req.peer_addr().ok_or(ApiError::Internal(
+        "Could not extract peer IP address from request",
+    ))?;
+    let ip = client_ip::client_ip(peer_addr.ip(), req.headers());
+
+    if config::get()
+        .api
+        .metrics_allowed
+        .iter()
+        .any(|net| net.contains(&ip))
+    {
+        Ok(())
+    } else {
+        Err(ApiError::RouteNotFound)
+    }
+}
+
+#[utoipa::path(summary = "Prometheus metrics", description = "Get metrics of the API and the menu refreshes in the Prometheus text format. Only available to clients allowed by the `API_METRICS_ALLOWED` setting.", responses(
+    (status = OK, description = "Get the current metrics.", body = String, content_type = "text/plain"),
+    (status = NOT_FOUND, description = "The client is not allowed to read metrics.", body = Problem, content_type = "application/problem+json"),
+))]
+#[get("/metrics")]
+async fn prometheus_metrics(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+    handle: web::Data<PrometheusHandle>,
+    menu_cache: web::Data<MenuCache>,
+) -> Result<HttpResponse, ApiError> {
+    ensure_allowed(&req)?;
+    metrics::record_db_pool(&db);
+    menu_cache.record_size();
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(handle.render()))
+}
@@ -1,19 +1,68 @@
-use actix_web::{get, HttpResponse, Responder};
+use actix_web::{get, middleware::from_fn, web, HttpResponse, Responder};
 use shared::Canteen;
 use strum::IntoEnumIterator as _;
+use utoipa::openapi::OpenApi;
 use utoipa_actix_web::service_config::ServiceConfig;
 
-mod menu;
-mod metadata;
-mod nutrition;
-mod price_history;
+use crate::deprecation;
 
+mod health;
+mod metrics;
+mod v1;
+mod v2;
+
+/// Versions of the API, each mounted under `/{version}` and described by its own OpenAPI
+/// document.
+pub const API_VERSIONS: [&str; 2] = ["v1", "v2"];
+
+/// Register the versioned API and the operational endpoints, which are not versioned.
 pub fn configure(cfg: &mut ServiceConfig) {
-    cfg.service(index)
-        .configure(metadata::configure)
-        .configure(menu::configure)
-        .configure(nutrition::configure)
-        .configure(price_history::configure);
+    cfg.configure(health::configure)
+        .configure(metrics::configure)
+        .service(utoipa_actix_web::scope("/v1").configure(v1::configure))
+        .service(utoipa_actix_web::scope("/v2").configure(v2::configure));
+}
+
+/// Register the routes of v1 at the root, where they were before the API was versioned.
+///
+/// Responses are marked as deprecated. This has to be registered after all other services, as
+/// it matches every path.
+pub fn configure_unversioned(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(deprecation::deprecate_unversioned))
+            // the index is registered at the root of the scopes, which does not match `/` here
+            .route("/", web::get().to(api_info))
+            .configure(|cfg| v1::configure(&mut ServiceConfig::new(cfg))),
+    );
+}
+
+/// Returns whether the path belongs to an operational endpoint, like health checks and metrics.
+pub(crate) fn is_operational(path: &str) -> bool {
+    path.starts_with("/health/") || path == "/metrics" || path.starts_with("/metrics/")
+}
+
+/// Returns whether the path belongs to the autocompletion of dish names, in any API version.
+pub(crate) fn is_autocomplete(path: &str) -> bool {
+    let unversioned = API_VERSIONS
+        .iter()
+        .find_map(|version| path.strip_prefix('/')?.strip_prefix(version))
+        .unwrap_or(path);
+    unversioned == "/dishes/suggest"
+}
+
+/// Get the OpenAPI document of a single API version out of the document of the whole app,
+/// which also lists the operational endpoints.
+pub fn openapi_for_version(api: &OpenApi, version: &str) -> OpenApi {
+    let root = format!("/{version}");
+    let mut api = api.clone();
+    api.paths.paths.retain(|path, _| {
+        path.strip_prefix(&root)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            || is_operational(path)
+    });
+    api.info.title = format!("{} {version}", api.info.title);
+    api
 }
 
 #[derive(serde::Serialize, utoipa::ToSchema)]
@@ -31,8 +80,12 @@ struct IndexResponse {
     description: env!("CARGO_PKG_DESCRIPTION"),
     supported_canteens: Canteen::iter().map(|c| c.get_identifier().to_string()).collect::<Vec<String>>()
 }))))]
-#[get("/")]
+#[get("")]
 async fn index() -> impl Responder {
+    api_info().await
+}
+
+async fn api_info() -> impl Responder {
     HttpResponse::Ok().json(IndexResponse {
         version: env!("CARGO_PKG_VERSION"),
         description: env!("CARGO_PKG_DESCRIPTION"),
@@ -0,0 +1,370 @@
+use std::str::FromStr as _;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::NaiveDate;
+use mensa_upb_scraper::today;
+use serde::{Deserialize, Serialize};
+use shared::Canteen;
+use sqlx::PgPool;
+use utoipa_actix_web::service_config::ServiceConfig;
+use uuid::Uuid;
+
+use crate::{
+    dish_search::{self, DishMatch, DishSearch, DishSuggestion},
+    error::{ApiError, Problem},
+    http_cache, util, DishPrices, SuggestionCache, UpcomingMenuRefresher,
+};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    // `search` and `suggest` have to be registered before `dish`, which matches every path segment
+    cfg.service(
+        utoipa_actix_web::scope("/dishes")
+            .service(search)
+            .service(suggest)
+            .service(dish)
+            .service(occurrences),
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct SearchQuery {
+    q: String,
+    canteens: Option<String>,
+    vegan: Option<bool>,
+    limit: Option<u32>,
+}
+
+#[utoipa::path(
+    summary = "Search dishes",
+    description = "Search dishes by name, tolerating typos and partial names. Dishes are ranked by the similarity of their names to the query.",
+    params(
+        ("q" = String, Query, description = "Name or part of the name of the dish", example = "curywurst"),
+        ("canteens" = Option<String>, Query, description = "Comma-separated list of canteen identifiers the dishes have to be served at", example = "forum,academica"),
+        ("vegan" = Option<bool>, Query, description = "Only return dishes that are (not) vegan"),
+        ("limit" = Option<u32>, Query, description = "Maximum number of dishes to return", minimum = 1, maximum = 100, example = 20),
+    ),
+    responses(
+        (status = OK, description = "Get the dishes matching the query, best matches first.", body = Vec<DishMatch>),
+        (status = NOT_MODIFIED, description = "The results have not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/search")]
+async fn search(
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let q = query.q.trim();
+    if q.is_empty() {
+        return Err(ApiError::InvalidQuery(
+            "The search query must not be empty".to_string(),
+        ));
+    }
+    let canteens = query
+        .canteens
+        .as_deref()
+        .map(util::parse_canteens_comma_separated)
+        .transpose()?;
+
+    let matches = dish_search::search_dishes(
+        db.as_ref(),
+        DishSearch {
+            query: q,
+            canteens: canteens.as_deref(),
+            vegan: query.vegan,
+            limit: query.limit.unwrap_or(20).clamp(1, 100).into(),
+        },
+    )
+    .await?;
+
+    Ok(http_cache::json_response(
+        &req,
+        &matches,
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
+}
+
+/// Minimum number of characters of a normalized autocompletion prefix.
+const MIN_SUGGEST_PREFIX_LENGTH: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct SuggestQuery {
+    prefix: String,
+    limit: Option<u32>,
+}
+
+#[utoipa::path(
+    summary = "Autocomplete dish names",
+    description = "Get the names of dishes starting with a prefix, for autocompletion as users type. Dishes served often and recently come first. This endpoint has its own, more generous rate limit.",
+    params(
+        ("prefix" = String, Query, description = "Beginning of the name of the dish, ignoring casing, punctuation and whitespace, with at least two other characters", example = "curryw"),
+        ("limit" = Option<u32>, Query, description = "Maximum number of names to return", minimum = 1, maximum = 25, example = 10),
+    ),
+    responses(
+        (status = OK, description = "Get the dishes completing the prefix, most relevant first.", body = Vec<DishSuggestion>),
+        (status = NOT_MODIFIED, description = "The suggestions have not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid query.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/suggest")]
+async fn suggest(
+    req: HttpRequest,
+    query: web::Query<SuggestQuery>,
+    db: web::Data<PgPool>,
+    suggestion_cache: web::Data<SuggestionCache>,
+) -> Result<HttpResponse, ApiError> {
+    // a single character matches too many dishes to be useful
+    let prefix = query.prefix.trim();
+    if dish_search::normalize_dish_name(prefix).chars().count() < MIN_SUGGEST_PREFIX_LENGTH {
+        return Err(ApiError::InvalidQuery(format!(
+            "The prefix must contain at least {MIN_SUGGEST_PREFIX_LENGTH} characters besides punctuation and whitespace"
+        )));
+    }
+    let limit = query.limit.unwrap_or(10).clamp(1, 25).into();
+
+    let suggestions = suggestion_cache
+        .get_or_query(db.as_ref(), prefix, limit)
+        .await?;
+
+    Ok(http_cache::json_response(
+        &req,
+        &*suggestions,
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+#[schema(examples(json!({
+    "id": "0b5a3e0c-5d0e-4a8b-9d4e-6f1c2a7b8c9d",
+    "name": "Bratwurst mit Currysauce und Pommes Frites",
+    "first_seen": "2024-11-08",
+    "last_seen": "2026-10-16",
+    "canteens": [
+        {
+            "canteen": "forum",
+            "first_seen": "2024-11-08",
+            "last_seen": "2026-10-16",
+            "price": {
+                "students": "3.5",
+                "employees": "5.1",
+                "guests": "6.5"
+            }
+        }
+    ]
+})))]
+struct DishDetails {
+    /// Stable identifier of the dish.
+    id: Uuid,
+    /// Name of the dish, as it was first seen.
+    name: String,
+    /// Date the dish was first served at any canteen.
+    first_seen: Option<NaiveDate>,
+    /// Date the dish was last served at any canteen, which may be in the future.
+    last_seen: Option<NaiveDate>,
+    /// The canteens the dish was served at.
+    canteens: Vec<DishServing>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct DishServing {
+    canteen: Canteen,
+    /// Date the dish was first served at the canteen.
+    first_seen: NaiveDate,
+    /// Date the dish was last served at the canteen.
+    last_seen: NaiveDate,
+    /// The price of the dish when it was last served at the canteen.
+    price: DishPrices,
+}
+
+#[utoipa::path(
+    summary = "Get a dish",
+    description = "Get a dish of the catalog by its stable identifier, with the dates and canteens it was served at and its current prices.",
+    params(("id" = Uuid, Path, description = "Identifier of the dish, as returned in menus")),
+    responses(
+        (status = OK, description = "Get a dish of the catalog.", body = DishDetails),
+        (status = NOT_MODIFIED, description = "The dish has not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Malformed dish identifier.", body = Problem, content_type = "application/problem+json"),
+        (status = NOT_FOUND, description = "No dish with the given identifier exists.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/{id}")]
+async fn dish(
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let db = db.as_ref();
+    let id = path.into_inner();
+
+    let dish = sqlx::query!("SELECT id, name FROM dishes WHERE id = $1", id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(ApiError::DishNotFound(Vec::new()))?;
+
+    let canteens = sqlx::query!(
+        r#"SELECT DISTINCT ON (canteen) canteen, MIN(date) OVER (PARTITION BY canteen) AS "first_seen!", date AS last_seen, price_students, price_employees, price_guests
+            FROM meals WHERE dish_id = $1 AND is_latest = TRUE
+            ORDER BY canteen, date DESC"#,
+        id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| DishServing {
+        canteen: Canteen::from_str(&row.canteen).expect("Invalid database entry"),
+        first_seen: row.first_seen,
+        last_seen: row.last_seen,
+        price: DishPrices {
+            students: row.price_students,
+            employees: row.price_employees,
+            guests: row.price_guests,
+        }
+        .normalize(),
+    })
+    .collect::<Vec<_>>();
+
+    let details = DishDetails {
+        id: dish.id,
+        name: dish.name,
+        first_seen: canteens.iter().map(|serving| serving.first_seen).min(),
+        last_seen: canteens.iter().map(|serving| serving.last_seen).max(),
+        canteens,
+    };
+
+    Ok(http_cache::json_response(
+        &req,
+        &details,
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct OccurrencesQuery {
+    limit: Option<u32>,
+    #[serde(default)]
+    no_update: bool,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+#[schema(examples(json!({
+    "id": "0b5a3e0c-5d0e-4a8b-9d4e-6f1c2a7b8c9d",
+    "name": "Bratwurst mit Currysauce und Pommes Frites",
+    "upcoming": [
+        { "date": "2026-10-21", "canteens": ["forum"] }
+    ],
+    "past": [
+        { "date": "2026-10-07", "canteens": ["academica", "forum"] },
+        { "date": "2026-09-23", "canteens": ["forum"] }
+    ]
+})))]
+struct DishOccurrences {
+    /// Stable identifier of the dish.
+    id: Uuid,
+    /// Name of the dish, as it was first seen.
+    name: String,
+    /// The days from today on the dish is on the menu, soonest first.
+    upcoming: Vec<Occurrence>,
+    /// The days before today the dish was on the menu, most recent first.
+    past: Vec<Occurrence>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct Occurrence {
+    date: NaiveDate,
+    /// The canteens serving the dish on that day.
+    canteens: Vec<Canteen>,
+}
+
+impl Occurrence {
+    fn new(date: NaiveDate, canteens: &[String]) -> Self {
+        Self {
+            date,
+            canteens: canteens
+                .iter()
+                .map(|canteen| Canteen::from_str(canteen).expect("Invalid database entry"))
+                .collect(),
+        }
+    }
+}
+
+#[utoipa::path(
+    summary = "Get the occurrences of a dish",
+    description = "Get the days and canteens a dish is or was on the menu at. Days start at midnight UTC. The menus of the upcoming days are updated in the background, so dishes only known from them may be found by later requests.",
+    params(
+        ("name" = String, Path, description = "Name of the dish, ignoring casing, punctuation and whitespace", example = "Bratwurst mit Currysauce und Pommes Frites"),
+        ("limit" = Option<u32>, Query, description = "Maximum number of past occurrences to return", minimum = 1, maximum = 1000, example = 100),
+        ("noUpdate" = Option<bool>, Query, description = "If set to true, the menus of the upcoming days will not be updated (default: false)", example = false),
+    ),
+    responses(
+        (status = OK, description = "Get the past and upcoming occurrences of a dish.", body = DishOccurrences),
+        (status = NOT_MODIFIED, description = "The occurrences have not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid query.", body = Problem, content_type = "application/problem+json"),
+        (status = NOT_FOUND, description = "No dish with a matching name could be found, the problem lists similar dishes as `suggestions`.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/{name}/occurrences")]
+async fn occurrences(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<OccurrencesQuery>,
+    db: web::Data<PgPool>,
+    refresher: web::Data<UpcomingMenuRefresher>,
+) -> Result<HttpResponse, ApiError> {
+    let db = db.as_ref();
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000) as i64;
+    let name = path.into_inner();
+    let today = today();
+
+    if !query.no_update {
+        refresher.into_inner().trigger(db);
+    }
+    let dish_id = dish_search::resolve_dish_name(db, &name).await?;
+
+    let name = sqlx::query_scalar!("SELECT name FROM dishes WHERE id = $1", dish_id)
+        .fetch_one(db)
+        .await?;
+
+    let upcoming = sqlx::query!(
+        r#"SELECT date, array_agg(DISTINCT canteen ORDER BY canteen) AS "canteens!"
+            FROM meals WHERE dish_id = $1 AND is_latest = TRUE AND date >= $2
+            GROUP BY date ORDER BY date"#,
+        dish_id,
+        today,
+    )
+    .map(|row| Occurrence::new(row.date, &row.canteens))
+    .fetch_all(db);
+    let past = sqlx::query!(
+        r#"SELECT date, array_agg(DISTINCT canteen ORDER BY canteen) AS "canteens!"
+            FROM meals WHERE dish_id = $1 AND is_latest = TRUE AND date < $2
+            GROUP BY date ORDER BY date DESC LIMIT $3"#,
+        dish_id,
+        today,
+        limit,
+    )
+    .map(|row| Occurrence::new(row.date, &row.canteens))
+    .fetch_all(db);
+    let (upcoming, past) = futures::try_join!(upcoming, past)?;
+
+    Ok(http_cache::json_response(
+        &req,
+        &DishOccurrences {
+            id: dish_id,
+            name,
+            upcoming,
+            past,
+        },
+        None,
+        http_cache::max_age_for_date(today),
+    ))
+}
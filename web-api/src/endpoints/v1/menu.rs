@@ -0,0 +1,174 @@
+use actix_web::{
+    get,
+    http::header::{CacheControl, CacheDirective},
+    web, HttpRequest, HttpResponse,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa_actix_web::service_config::ServiceConfig;
+
+use crate::{
+    error::{ApiError, Problem},
+    http_cache,
+    menu_history::MenuHistory,
+    menu_stream, util, Menu, MenuCache, MenuChangeNotifier,
+};
+
+/// Time after which a past version of a menu cannot change anymore. Meals are stored with the
+/// start time of the transaction saving a scrape, so a running scrape may still add meals to
+/// the last few minutes.
+const VERSION_SETTLED_AFTER: Duration = Duration::minutes(10);
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(menu).service(menu_history).service(menu_events);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct MenuQuery {
+    date: Option<NaiveDate>,
+    #[serde(default)]
+    no_update: bool,
+    as_of: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    summary = "Get menu of canteen(s)", 
+    description = "Get the menu of a canteen(s) (at specified date), optionally as it was known at a past moment.", 
+    params(
+        ("canteens" = String, Path, description = "Comma-separated list of canteen identifiers to get the menu for", example = "forum,academica"),
+        ("date" = Option<NaiveDate>, Query, description = "Date to get the menu for (defaults to today)"),
+        ("noUpdate" = Option<bool>, Query, description = "If set to true, the menu will not be updated before querying (default: false). Menus are cached for a few minutes, so a due update may be delayed until the cached menu expires.", example = false),
+        ("asOf" = Option<DateTime<Utc>>, Query, description = "Reconstruct the menu as it was known at this moment (RFC 3339) instead of getting the latest one, the menu is not updated then. The `+` of a positive UTC offset has to be percent-encoded as `%2B` (e.g. `2026-10-19T10:00:00%2B02:00`), as it is decoded as a space otherwise.", example = "2026-10-19T08:00:00Z"),
+    ),
+    responses(
+        (status = OK, description = "The menu of the specified canteen(s).", body = [Menu]),
+        (status = NOT_MODIFIED, description = "The menu has not changed since the version identified by `If-None-Match` or `If-Modified-Since`."),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/menu/{canteens}")]
+async fn menu(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<MenuQuery>,
+    db: web::Data<PgPool>,
+    menu_cache: web::Data<MenuCache>,
+) -> Result<HttpResponse, ApiError> {
+    let canteens = util::parse_canteens_comma_separated(&path)?;
+
+    let date = query
+        .date
+        .unwrap_or_else(|| chrono::Local::now().date_naive());
+
+    if let Some(as_of) = query.as_of {
+        if as_of > Utc::now() {
+            return Err(ApiError::InvalidQuery(format!(
+                "The menu can only be reconstructed for past moments, not {as_of}"
+            )));
+        }
+
+        // past versions never change, so they are not cached with the latest menus
+        let menu = Menu::query_at(&db, date, &canteens, as_of).await?;
+        return Ok(if Utc::now() - as_of >= VERSION_SETTLED_AFTER {
+            http_cache::immutable_json_response(&req, &menu)
+        } else {
+            http_cache::json_response(&req, &menu, None, http_cache::max_age_for_date(date))
+        });
+    }
+
+    let cached = menu_cache
+        .get_or_query(&db, date, &canteens, !query.no_update)
+        .await?;
+
+    Ok(http_cache::json_response(
+        &req,
+        &cached.menu,
+        cached.last_modified,
+        http_cache::max_age_for_date(date),
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct MenuHistoryQuery {
+    date: Option<NaiveDate>,
+}
+
+#[utoipa::path(
+    summary = "Get menu history of canteen(s)",
+    description = "Get all versions of the menu of canteen(s) (at specified date) as they were scraped over time, each with the dishes added, removed and changed compared to the previous version.",
+    params(
+        ("canteens" = String, Path, description = "Comma-separated list of canteen identifiers to get the menu history for", example = "forum,academica"),
+        ("date" = Option<NaiveDate>, Query, description = "Date to get the menu history for (defaults to today)"),
+    ),
+    responses(
+        (status = OK, description = "The versions of the menu of the specified canteen(s).", body = MenuHistory),
+        (status = NOT_MODIFIED, description = "The menu history has not changed since the version identified by `If-None-Match` or `If-Modified-Since`."),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/menu/{canteens}/history")]
+async fn menu_history(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<MenuHistoryQuery>,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let canteens = util::parse_canteens_comma_separated(&path)?;
+
+    let date = query
+        .date
+        .unwrap_or_else(|| chrono::Local::now().date_naive());
+
+    let history = MenuHistory::query(&db, date, &canteens).await?;
+
+    Ok(http_cache::json_response(
+        &req,
+        &history,
+        history.last_scraped_at(),
+        http_cache::max_age_for_date(date),
+    ))
+}
+
+#[utoipa::path(
+    summary = "Stream menu updates of canteen(s)",
+    description = "Open a stream of server-sent events containing the menu of the canteen(s) (at specified date). The current menu is sent immediately as a `menu` event, followed by the updated menu whenever it changes. Without a date, the stream follows the current day.",
+    params(
+        ("canteens" = String, Path, description = "Comma-separated list of canteen identifiers to get the menu for", example = "forum,academica"),
+        ("date" = Option<NaiveDate>, Query, description = "Date to get the menu for (defaults to the current day)"),
+        ("noUpdate" = Option<bool>, Query, description = "If set to true, the menu will not be updated before sending the initial menu (default: false)", example = false),
+    ),
+    responses(
+        (status = OK, description = "Stream of `menu` events containing the menu of the specified canteen(s).", content_type = "text/event-stream", body = Menu),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/menu/{canteens}/events")]
+async fn menu_events(
+    path: web::Path<String>,
+    query: web::Query<MenuQuery>,
+    db: web::Data<PgPool>,
+    menu_cache: web::Data<MenuCache>,
+    notifier: web::Data<MenuChangeNotifier>,
+) -> Result<HttpResponse, ApiError> {
+    let canteens = util::parse_canteens_comma_separated(&path)?;
+
+    let stream = menu_stream::menu_updates(
+        db.as_ref().clone(),
+        menu_cache.into_inner(),
+        notifier.subscribe(),
+        canteens,
+        query.date,
+        !query.no_update,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream))
+}
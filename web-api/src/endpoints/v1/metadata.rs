@@ -1,13 +1,15 @@
 use std::sync::OnceLock;
 
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, web, HttpRequest, HttpResponse};
 use chrono::NaiveDate;
 use serde::Serialize;
-use serde_json::json;
 use sqlx::PgPool;
 use utoipa_actix_web::service_config::ServiceConfig;
 
-use crate::util::GenericServerError;
+use crate::{
+    error::{ApiError, Problem},
+    http_cache,
+};
 
 pub fn configure(cfg: &mut ServiceConfig) {
     cfg.service(utoipa_actix_web::scope("/metadata").service(earliest_meal_date));
@@ -22,31 +24,30 @@ struct DateResponse {
 
 #[utoipa::path(summary = "Earliest meal date", description = "Get the date of the earliest meal saved.", responses(
     (status = OK, description = "Get the date of the earliest meal saved.", body = DateResponse), 
-    (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = GenericServerError)
+    (status = NOT_MODIFIED, description = "The date has not changed since the version identified by `If-None-Match`."),
+    (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
 ))]
 #[get("/earliest-meal-date")]
-async fn earliest_meal_date(db: web::Data<PgPool>) -> impl Responder {
-    if let Some(earliest_date) = EARLIEST_MEAL_DATE.get() {
-        HttpResponse::Ok().json(DateResponse {
-            date: *earliest_date,
-        })
+async fn earliest_meal_date(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let date = if let Some(earliest_date) = EARLIEST_MEAL_DATE.get() {
+        *earliest_date
     } else {
-        match sqlx::query_scalar!(
+        let date = sqlx::query_scalar!(
             r#"SELECT MIN(date) AS "date!" FROM meals WHERE is_latest = TRUE;"#
         )
         .fetch_one(db.as_ref())
-        .await
-        {
-            Ok(date) => {
-                EARLIEST_MEAL_DATE.set(date).ok();
-                HttpResponse::Ok().json(DateResponse { date })
-            }
-            Err(err) => {
-                tracing::error!("Failed to query datebase: {err}");
-                HttpResponse::InternalServerError().json(json!({
-                    "error": "Failed to query database"
-                }))
-            }
-        }
-    }
+        .await?;
+        EARLIEST_MEAL_DATE.set(date).ok();
+        date
+    };
+
+    Ok(http_cache::json_response(
+        &req,
+        &DateResponse { date },
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
 }
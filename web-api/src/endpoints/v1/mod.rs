@@ -0,0 +1,20 @@
+use utoipa_actix_web::service_config::ServiceConfig;
+
+mod dishes;
+mod menu;
+mod metadata;
+mod nutrition;
+mod price_changes;
+mod price_history;
+mod stats;
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(super::index)
+        .configure(metadata::configure)
+        .configure(menu::configure)
+        .configure(dishes::configure)
+        .configure(nutrition::configure)
+        .configure(price_history::configure)
+        .configure(price_changes::configure)
+        .configure(stats::configure);
+}
@@ -1,11 +1,15 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, web, HttpRequest, HttpResponse};
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use sqlx::PgPool;
 use utoipa_actix_web::service_config::ServiceConfig;
 
-use crate::{dish::DishNutrients, util::GenericServerError};
+use crate::{
+    dish::DishNutrients,
+    dish_search,
+    error::{ApiError, Problem},
+    http_cache,
+};
 
 pub fn configure(cfg: &mut ServiceConfig) {
     cfg.service(nutrition);
@@ -23,44 +27,43 @@ struct NutritionQuery {
     params(("name" = String, Path, description = "Name of the dish to query nutrition values for", example = "Bratwurst mit Currysauce und Pommes Frites")),
     responses(
         (status = OK, description = "Get nutrition values of some dish.", body = DishNutrients),
-        (status = NOT_FOUND, description = "No dish with a matching name could be found.", body = GenericServerError),
-        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = GenericServerError)
+        (status = NOT_MODIFIED, description = "The nutrition values have not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid query.", body = Problem, content_type = "application/problem+json"),
+        (status = NOT_FOUND, description = "No dish with a matching name could be found, the problem lists similar dishes as `suggestions` if the name is unknown.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
     )
 )]
 #[get("/nutrition/{name}")]
 async fn nutrition(
+    req: HttpRequest,
     path: web::Path<String>,
     query: web::Query<NutritionQuery>,
     db: web::Data<PgPool>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let db = db.as_ref();
-    let dish_name = path.into_inner();
+    let dish_id = dish_search::resolve_dish_name(db, &path.into_inner()).await?;
 
     let res = if let Some(date) = query.date {
         sqlx::query_as!(
             DishNutrients,
-            r#"SELECT kjoules, proteins, carbohydrates, fats FROM meals m WHERE is_latest = TRUE AND LOWER("name") = $1 AND date = $2 LIMIT 1;"#,
-            dish_name.to_lowercase(),
+            r#"SELECT kjoules, proteins, carbohydrates, fats FROM meals m WHERE is_latest = TRUE AND dish_id = $1 AND date = $2 LIMIT 1;"#,
+            dish_id,
             date,
         ).fetch_optional(db).await
     } else {
         sqlx::query_as!(
             DishNutrients,
-            r#"SELECT kjoules, proteins, carbohydrates, fats FROM meals m WHERE is_latest = TRUE AND LOWER("name") = $1 ORDER BY date DESC LIMIT 1;"#,
-            dish_name.to_lowercase(),
+            r#"SELECT kjoules, proteins, carbohydrates, fats FROM meals m WHERE is_latest = TRUE AND dish_id = $1 ORDER BY date DESC LIMIT 1;"#,
+            dish_id,
         ).fetch_optional(db).await
     };
 
-    match res {
-        Ok(Some(nutrition)) => HttpResponse::Ok().json(nutrition.normalize()),
-        Ok(None) => HttpResponse::NotFound().json(json!({
-            "error": "Dish cannot be found",
-        })),
-        Err(err) => {
-            tracing::error!("Failed to query database: {err:?}");
-            HttpResponse::InternalServerError().json(json!({
-                "error": "Failed to query database",
-            }))
-        }
-    }
+    let nutrition = res?.ok_or(ApiError::DishNotFound(Vec::new()))?;
+
+    Ok(http_cache::json_response(
+        &req,
+        &nutrition.normalize(),
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
 }
@@ -0,0 +1,210 @@
+use std::str::FromStr as _;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::{Duration, NaiveDate};
+use itertools::Itertools as _;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use shared::Canteen;
+use sqlx::PgPool;
+use strum::IntoEnumIterator as _;
+use utoipa_actix_web::service_config::ServiceConfig;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, Problem},
+    http_cache, util, DishPrices,
+};
+
+/// Maximum number of days price changes are listed for, as every change since then is listed.
+const MAX_DAYS: i64 = 90;
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(price_changes);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct PriceChangesQuery {
+    since: Option<NaiveDate>,
+    canteens: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct PriceChanges {
+    /// First day on which changed prices are listed, which is at most 90 days ago.
+    since: NaiveDate,
+    /// The price changes, newest first.
+    changes: Vec<PriceChange>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+#[schema(examples(json!({
+    "id": "0b5a3e0c-5d0e-4a8b-9d4e-6f1c2a7b8c9d",
+    "name": "Bratwurst mit Currysauce und Pommes Frites",
+    "canteen": "forum",
+    "date": "2026-10-14",
+    "previous_date": "2026-10-07",
+    "old_price": {
+        "students": "3.1",
+        "employees": "4.3",
+        "guests": "5.5"
+    },
+    "new_price": {
+        "students": "3.3",
+        "employees": "4.5",
+        "guests": "5.7"
+    },
+    "change_percent": {
+        "students": "6.5",
+        "employees": "4.7",
+        "guests": "3.6"
+    }
+})))]
+struct PriceChange {
+    id: Uuid,
+    name: String,
+    canteen: Canteen,
+    /// The day the dish was served with the new prices.
+    date: NaiveDate,
+    /// The previous day the dish was served at the canteen, with the old prices.
+    previous_date: NaiveDate,
+    old_price: DishPrices,
+    new_price: DishPrices,
+    change_percent: PriceChangePercentages,
+}
+
+/// Relative change of each price in percent, rounded to one decimal place. Missing if the old
+/// price was zero.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+struct PriceChangePercentages {
+    students: Option<Decimal>,
+    employees: Option<Decimal>,
+    guests: Option<Decimal>,
+}
+
+impl PriceChangePercentages {
+    fn new(old: &DishPrices, new: &DishPrices) -> Self {
+        let percent = |old: Decimal, new: Decimal| {
+            (!old.is_zero()).then(|| {
+                ((new - old) / old * Decimal::ONE_HUNDRED)
+                    .round_dp(1)
+                    .normalize()
+            })
+        };
+
+        Self {
+            students: percent(old.students, new.students),
+            employees: percent(old.employees, new.employees),
+            guests: percent(old.guests, new.guests),
+        }
+    }
+}
+
+#[utoipa::path(
+    summary = "Get price changes",
+    description = "List every serving of a dish since the given day whose prices differ from the previous serving of the dish at the same canteen.",
+    params(
+        ("since" = Option<NaiveDate>, Query, description = "First day to list price changes for (defaults to a week ago, at most 90 days ago)"),
+        ("canteens" = Option<String>, Query, description = "Comma-separated list of canteen identifiers to list price changes for (defaults to all canteens)", example = "forum,academica"),
+    ),
+    responses(
+        (status = OK, description = "List the price changes since the given day.", body = PriceChanges),
+        (status = NOT_MODIFIED, description = "The price changes have not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/price-changes")]
+async fn price_changes(
+    req: HttpRequest,
+    query: web::Query<PriceChangesQuery>,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let today = chrono::Local::now().date_naive();
+    let since = query
+        .since
+        .unwrap_or(today - Duration::days(7))
+        .max(today - Duration::days(MAX_DAYS));
+    let canteens = query
+        .canteens
+        .as_deref()
+        .map(util::parse_canteens_comma_separated)
+        .transpose()?
+        .unwrap_or_else(|| Canteen::iter().collect())
+        .iter()
+        .map(|c| c.get_identifier().to_string())
+        .collect_vec();
+
+    // the previous serving may be before `since`, so the whole history of the affected dishes
+    // is windowed before filtering. A dish may be served more than once a day at a canteen
+    // (e.g. under different spellings), the cheapest serving counts then.
+    let changes = sqlx::query!(
+        r#"WITH daily AS (
+                SELECT DISTINCT ON (dish_id, canteen, date)
+                    dish_id, canteen, date, price_students, price_employees, price_guests
+                FROM meals
+                WHERE is_latest = TRUE AND canteen = ANY($2)
+                    AND dish_id IN (SELECT dish_id FROM meals WHERE is_latest = TRUE AND date >= $1)
+                ORDER BY dish_id, canteen, date, price_students, price_employees, price_guests
+            ), servings AS (
+                SELECT dish_id, canteen, date, price_students, price_employees, price_guests,
+                    LAG(date) OVER w AS previous_date,
+                    LAG(price_students) OVER w AS previous_students,
+                    LAG(price_employees) OVER w AS previous_employees,
+                    LAG(price_guests) OVER w AS previous_guests
+                FROM daily
+                WINDOW w AS (PARTITION BY dish_id, canteen ORDER BY date)
+            )
+            SELECT d.id, d.name, s.canteen, s.date,
+                s.previous_date AS "previous_date!",
+                s.price_students, s.price_employees, s.price_guests,
+                s.previous_students AS "previous_students!",
+                s.previous_employees AS "previous_employees!",
+                s.previous_guests AS "previous_guests!"
+            FROM servings s
+            JOIN dishes d ON d.id = s.dish_id
+            WHERE s.date >= $1 AND s.previous_date IS NOT NULL
+                AND (s.price_students, s.price_employees, s.price_guests)
+                    IS DISTINCT FROM (s.previous_students, s.previous_employees, s.previous_guests)
+            ORDER BY s.date DESC, s.canteen, d.name"#,
+        since,
+        &canteens,
+    )
+    .fetch_all(db.as_ref())
+    .await?
+    .into_iter()
+    .map(|row| {
+        let old_price = DishPrices {
+            students: row.previous_students,
+            employees: row.previous_employees,
+            guests: row.previous_guests,
+        }
+        .normalize();
+        let new_price = DishPrices {
+            students: row.price_students,
+            employees: row.price_employees,
+            guests: row.price_guests,
+        }
+        .normalize();
+
+        PriceChange {
+            id: row.id,
+            name: row.name,
+            canteen: Canteen::from_str(&row.canteen).expect("Invalid database entry"),
+            date: row.date,
+            previous_date: row.previous_date,
+            change_percent: PriceChangePercentages::new(&old_price, &new_price),
+            old_price,
+            new_price,
+        }
+    })
+    .collect();
+
+    Ok(http_cache::json_response(
+        &req,
+        &PriceChanges { since, changes },
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
+}
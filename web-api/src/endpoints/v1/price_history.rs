@@ -0,0 +1,538 @@
+use std::{collections::BTreeMap, str::FromStr as _};
+
+use actix_web::{
+    get,
+    http::header::{HeaderValue, LINK},
+    web, HttpRequest, HttpResponse,
+};
+use chrono::NaiveDate;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use shared::Canteen;
+use sqlx::PgPool;
+use utoipa_actix_web::service_config::ServiceConfig;
+
+use crate::{
+    dish_search,
+    error::{ApiError, Problem},
+    http_cache,
+    util::{self, Granularity},
+    DishPrices,
+};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(price_history);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct PriceHistoryQuery {
+    canteens: Option<String>,
+    limit: Option<u32>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    #[serde(default)]
+    mode: PriceHistoryMode,
+    granularity: Option<Granularity>,
+    cursor: Option<String>,
+}
+
+/// How the prices of a dish are listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum PriceHistoryMode {
+    /// The prices of every day (or period) the dish was served.
+    #[default]
+    All,
+    /// Intervals of consecutive days (or periods) with identical prices.
+    Changes,
+}
+
+/// Prices of a dish per canteen.
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+#[serde(untagged)]
+enum PriceHistory {
+    /// The prices per day (or first day of the period) for `mode=all`.
+    All(BTreeMap<String, BTreeMap<NaiveDate, DishPrices>>),
+    /// The intervals of identical prices, in chronological order, for `mode=changes`.
+    Changes(BTreeMap<String, Vec<PriceInterval>>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+struct PriceInterval {
+    /// First day (or first day of the first period) with these prices.
+    from: NaiveDate,
+    /// Last day (or first day of the last period) with these prices.
+    to: NaiveDate,
+    prices: DishPrices,
+}
+
+/// An entry of the price history, which is paginated newest first.
+#[derive(Debug, Clone)]
+struct PriceHistoryEntry {
+    canteen: Canteen,
+    date: NaiveDate,
+    kind: EntryKind,
+}
+
+#[derive(Debug, Clone)]
+enum EntryKind {
+    Prices(DishPrices),
+    Interval(PriceInterval),
+}
+
+/// Position in the price history after which the next page starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    date: NaiveDate,
+    canteen: Canteen,
+}
+
+impl Cursor {
+    fn parse(cursor: &str) -> Result<Self, ApiError> {
+        let invalid = || ApiError::InvalidQuery(format!("Invalid cursor: {cursor}"));
+        let (date, canteen) = cursor.split_once('.').ok_or_else(invalid)?;
+
+        Ok(Self {
+            date: date.parse().map_err(|_| invalid())?,
+            canteen: Canteen::from_str(canteen).map_err(|_| invalid())?,
+        })
+    }
+
+    fn precedes(&self, entry: &PriceHistoryEntry) -> bool {
+        (entry.date, self.canteen) < (self.date, entry.canteen)
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.date, self.canteen.get_identifier())
+    }
+}
+
+#[utoipa::path(
+    summary = "Get price history of a dish",
+    description = "Query the price history of a dish (optionally filtered by canteen(s) and date). Prices can be averaged per week or month and collapsed into intervals of identical prices. Results are paginated newest first: if there are more entries, the `Link` header with `rel=\"next\"` points to the next page.",
+    params(
+        ("name" = String, Path, description = "Name of the dish to query price history for", example = "Bratwurst mit Currysauce und Pommes Frites"),
+        ("canteens" = Option<String>, Query, description = "Comma-separated list of canteen identifiers to filter the price history by", example = "forum,academica"),
+        ("limit" = Option<u32>, Query, description = "Maximum number of entries (prices or intervals) per page", minimum = 1, maximum = 1000, example = 100),
+        ("from" = Option<NaiveDate>, Query, description = "Only include prices from this day on"),
+        ("to" = Option<NaiveDate>, Query, description = "Only include prices up to this day"),
+        ("mode" = Option<PriceHistoryMode>, Query, description = "`all` to list the prices of every day (default), `changes` to list intervals of identical prices"),
+        ("granularity" = Option<Granularity>, Query, description = "Average the prices per week or month instead of listing the prices of every day"),
+        ("cursor" = Option<String>, Query, description = "Opaque position to continue from, taken from the `Link` header of the previous page"),
+    ),
+    responses(
+        (status = OK, description = "Query the price history of a dish.", body = PriceHistory, example = json!({
+            "forum": {
+                "2024-06-01": {
+                    "students": "2.50",
+                    "employees": "3.50",
+                    "guests": "4.50"
+                },
+                "2024-05-31": {
+                    "students": "2.40",
+                    "employees": "3.40",
+                    "guests": "4.40"
+                }
+            },
+            "academica": {
+                "2024-06-01": {
+                    "students": "2.60",
+                    "employees": "3.60",
+                    "guests": "4.60"
+                }
+            }
+        })),
+        (status = NOT_MODIFIED, description = "The price history has not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+        (status = NOT_FOUND, description = "No dish with a matching name could be found, the problem lists similar dishes as `suggestions`.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/price-history/{name}")]
+async fn price_history(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PriceHistoryQuery>,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let db = db.as_ref();
+    let canteens = query
+        .canteens
+        .as_deref()
+        .map(util::parse_canteens_comma_separated)
+        .transpose()?
+        .map(|canteens| {
+            canteens
+                .iter()
+                .map(|c| c.get_identifier().to_string())
+                .collect_vec()
+        });
+    let cursor = query.cursor.as_deref().map(Cursor::parse).transpose()?;
+    let dish_id = dish_search::resolve_dish_name(db, &path.into_inner()).await?;
+    let limit = query.limit.unwrap_or(1000).clamp(1, 1000) as usize;
+
+    // entries after the cursor only contain days up to the end of its period, but an interval
+    // of identical prices may extend beyond the cursor, so all later days are needed to find its
+    // end
+    let to = match (query.mode, cursor) {
+        (PriceHistoryMode::All, Some(cursor)) => {
+            let cursor_end = match query.granularity {
+                Some(granularity) => granularity.period_end(cursor.date),
+                None => cursor.date,
+            };
+            Some(query.to.map_or(cursor_end, |to| to.min(cursor_end)))
+        }
+        _ => query.to,
+    };
+
+    // the history of a single dish is small, so it is aggregated and paginated in memory. A dish
+    // may be served more than once a day at a canteen (e.g. under different spellings), the
+    // cheapest serving counts then, like for price changes.
+    let series = sqlx::query!(
+        r#"SELECT DISTINCT ON (canteen, date) date, canteen, price_students, price_employees, price_guests FROM meals
+            WHERE dish_id = $1 AND is_latest = TRUE AND ($2::text[] IS NULL OR canteen = ANY($2))
+                AND ($3::date IS NULL OR date >= $3) AND ($4::date IS NULL OR date <= $4)
+            ORDER BY canteen, date, price_students, price_employees, price_guests"#,
+        dish_id,
+        canteens.as_deref(),
+        query.from,
+        to,
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|row| {
+        (
+            Canteen::from_str(&row.canteen).expect("Invalid database entry"),
+            (
+                row.date,
+                DishPrices {
+                    students: row.price_students,
+                    employees: row.price_employees,
+                    guests: row.price_guests,
+                }
+                .normalize(),
+            ),
+        )
+    })
+    .into_group_map();
+
+    let entries = series.into_iter().flat_map(|(canteen, series)| {
+        let series = match query.granularity {
+            Some(granularity) => average_per_period(series, granularity),
+            None => series,
+        };
+        let kinds = match query.mode {
+            PriceHistoryMode::All => series
+                .into_iter()
+                .map(|(date, prices)| (date, EntryKind::Prices(prices)))
+                .collect_vec(),
+            PriceHistoryMode::Changes => collapse_changes(series)
+                .into_iter()
+                .map(|interval| (interval.from, EntryKind::Interval(interval)))
+                .collect_vec(),
+        };
+        kinds
+            .into_iter()
+            .map(move |(date, kind)| PriceHistoryEntry {
+                canteen,
+                date,
+                kind,
+            })
+    });
+    let (entries, next) = paginate(entries, cursor, limit);
+
+    let history = match query.mode {
+        PriceHistoryMode::All => PriceHistory::All(
+            entries
+                .into_iter()
+                .filter_map(|entry| match entry.kind {
+                    EntryKind::Prices(prices) => Some((entry.canteen, (entry.date, prices))),
+                    EntryKind::Interval(_) => None,
+                })
+                .into_grouping_map()
+                .collect()
+                .into_iter()
+                .map(|(canteen, prices)| (canteen.get_identifier().to_string(), prices))
+                .collect(),
+        ),
+        PriceHistoryMode::Changes => PriceHistory::Changes(
+            entries
+                .into_iter()
+                .filter_map(|entry| match entry.kind {
+                    EntryKind::Interval(interval) => Some((entry.canteen, interval)),
+                    EntryKind::Prices(_) => None,
+                })
+                .into_group_map()
+                .into_iter()
+                .map(|(canteen, intervals)| {
+                    (
+                        canteen.get_identifier().to_string(),
+                        intervals.into_iter().rev().collect(),
+                    )
+                })
+                .collect(),
+        ),
+    };
+
+    let mut response = http_cache::json_response(&req, &history, None, http_cache::DEFAULT_MAX_AGE);
+
+    if let Some(next) = next {
+        let query = req
+            .query_string()
+            .split('&')
+            .filter(|pair| !pair.is_empty() && !pair.starts_with("cursor="))
+            .chain([format!("cursor={next}").as_str()])
+            .join("&");
+        if let Ok(link) = HeaderValue::from_str(&format!("<{}?{query}>; rel=\"next\"", req.path()))
+        {
+            response.headers_mut().append(LINK, link);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Get the page of entries after the cursor, newest first, and the cursor of the next page if
+/// there are more entries.
+fn paginate(
+    entries: impl IntoIterator<Item = PriceHistoryEntry>,
+    cursor: Option<Cursor>,
+    limit: usize,
+) -> (Vec<PriceHistoryEntry>, Option<Cursor>) {
+    let mut entries = entries
+        .into_iter()
+        .filter(|entry| cursor.is_none_or(|cursor| cursor.precedes(entry)))
+        .sorted_by(|a, b| b.date.cmp(&a.date).then_with(|| a.canteen.cmp(&b.canteen)))
+        .take(limit + 1)
+        .collect_vec();
+
+    let next = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|last| Cursor {
+            date: last.date,
+            canteen: last.canteen,
+        })
+    } else {
+        None
+    };
+
+    (entries, next)
+}
+
+/// Average the prices of a chronological series per period, keyed by the first day of the
+/// period.
+fn average_per_period(
+    series: Vec<(NaiveDate, DishPrices)>,
+    granularity: Granularity,
+) -> Vec<(NaiveDate, DishPrices)> {
+    series
+        .into_iter()
+        .chunk_by(|(date, _)| granularity.period_start(*date))
+        .into_iter()
+        .map(|(start, prices)| {
+            let prices = prices.map(|(_, prices)| prices).collect_vec();
+            let count = Decimal::from(prices.len());
+            let average = |price: fn(&DishPrices) -> Decimal| {
+                prices.iter().map(price).sum::<Decimal>() / count
+            };
+
+            (
+                start,
+                DishPrices {
+                    students: average(|p| p.students),
+                    employees: average(|p| p.employees),
+                    guests: average(|p| p.guests),
+                }
+                .normalize(),
+            )
+        })
+        .collect()
+}
+
+/// Collapse consecutive entries of a chronological series with identical prices into intervals.
+fn collapse_changes(series: Vec<(NaiveDate, DishPrices)>) -> Vec<PriceInterval> {
+    series
+        .into_iter()
+        .map(|(date, prices)| PriceInterval {
+            from: date,
+            to: date,
+            prices,
+        })
+        .coalesce(|current, next| {
+            if current.prices == next.prices {
+                Ok(PriceInterval {
+                    to: next.to,
+                    ..current
+                })
+            } else {
+                Err((current, next))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn prices(students: i64) -> DishPrices {
+        DishPrices {
+            students: Decimal::new(students, 2),
+            employees: Decimal::new(students + 100, 2),
+            guests: Decimal::new(students + 200, 2),
+        }
+    }
+
+    fn entry(day: &str, canteen: Canteen) -> PriceHistoryEntry {
+        PriceHistoryEntry {
+            canteen,
+            date: date(day),
+            kind: EntryKind::Prices(prices(250)),
+        }
+    }
+
+    fn positions(entries: &[PriceHistoryEntry]) -> Vec<(NaiveDate, Canteen)> {
+        entries
+            .iter()
+            .map(|entry| (entry.date, entry.canteen))
+            .collect()
+    }
+
+    #[test]
+    fn cursor_precedes_older_entries_and_later_canteens_of_the_same_day() {
+        let cursor = Cursor {
+            date: date("2026-10-14"),
+            canteen: Canteen::Academica,
+        };
+
+        assert!(cursor.precedes(&entry("2026-10-13", Canteen::Forum)));
+        assert!(cursor.precedes(&entry("2026-10-14", Canteen::Picknick)));
+        assert!(!cursor.precedes(&entry("2026-10-14", Canteen::Academica)));
+        assert!(!cursor.precedes(&entry("2026-10-14", Canteen::Forum)));
+        assert!(!cursor.precedes(&entry("2026-10-15", Canteen::Picknick)));
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = Cursor {
+            date: date("2026-10-14"),
+            canteen: Canteen::GrillCafe,
+        };
+
+        assert_eq!(Cursor::parse(&cursor.to_string()).unwrap(), cursor);
+        assert!(Cursor::parse("2026-10-14").is_err());
+        assert!(Cursor::parse("2026-10-14.mensa").is_err());
+    }
+
+    #[test]
+    fn pages_split_entries_of_the_same_day_at_multiple_canteens() {
+        let entries = [
+            entry("2026-10-13", Canteen::Forum),
+            entry("2026-10-14", Canteen::Picknick),
+            entry("2026-10-14", Canteen::Forum),
+            entry("2026-10-14", Canteen::Academica),
+        ];
+
+        let (first, cursor) = paginate(entries.clone(), None, 2);
+        assert_eq!(
+            positions(&first),
+            [
+                (date("2026-10-14"), Canteen::Forum),
+                (date("2026-10-14"), Canteen::Academica),
+            ]
+        );
+        let cursor = cursor.expect("there is a second page");
+
+        let (second, cursor) = paginate(entries, Some(cursor), 2);
+        assert_eq!(
+            positions(&second),
+            [
+                (date("2026-10-14"), Canteen::Picknick),
+                (date("2026-10-13"), Canteen::Forum),
+            ]
+        );
+        assert_eq!(cursor, None);
+    }
+
+    #[test]
+    fn single_entry_collapses_into_a_single_day_interval() {
+        let intervals = collapse_changes(vec![(date("2026-10-14"), prices(250))]);
+
+        assert_eq!(
+            intervals,
+            [PriceInterval {
+                from: date("2026-10-14"),
+                to: date("2026-10-14"),
+                prices: prices(250),
+            }]
+        );
+    }
+
+    #[test]
+    fn changes_collapse_identical_consecutive_prices_only() {
+        let intervals = collapse_changes(vec![
+            (date("2026-10-12"), prices(250)),
+            (date("2026-10-13"), prices(250)),
+            (date("2026-10-14"), prices(270)),
+            (date("2026-10-15"), prices(250)),
+        ]);
+
+        assert_eq!(
+            intervals
+                .iter()
+                .map(|interval| (interval.from, interval.to))
+                .collect_vec(),
+            [
+                (date("2026-10-12"), date("2026-10-13")),
+                (date("2026-10-14"), date("2026-10-14")),
+                (date("2026-10-15"), date("2026-10-15")),
+            ]
+        );
+    }
+
+    #[test]
+    fn weeks_spanning_a_month_change_are_averaged_together() {
+        let series = vec![
+            (date("2026-09-29"), prices(200)),
+            (date("2026-09-30"), prices(300)),
+            (date("2026-10-01"), prices(400)),
+            (date("2026-10-05"), prices(500)),
+        ];
+
+        assert_eq!(
+            average_per_period(series.clone(), Granularity::Week),
+            [
+                (date("2026-09-28"), prices(300)),
+                (date("2026-10-05"), prices(500)),
+            ]
+        );
+        assert_eq!(
+            average_per_period(series, Granularity::Month),
+            [
+                (date("2026-09-01"), prices(250)),
+                (date("2026-10-01"), prices(450)),
+            ]
+        );
+    }
+
+    #[test]
+    fn periods_end_on_their_last_day() {
+        assert_eq!(
+            Granularity::Week.period_end(date("2026-09-30")),
+            date("2026-10-04")
+        );
+        assert_eq!(
+            Granularity::Month.period_end(date("2026-02-10")),
+            date("2026-02-28")
+        );
+    }
+}
@@ -0,0 +1,586 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr as _,
+};
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::{Datelike as _, Duration, NaiveDate};
+use itertools::Itertools as _;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use shared::Canteen;
+use sqlx::PgPool;
+use strum::IntoEnumIterator as _;
+use utoipa_actix_web::service_config::ServiceConfig;
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, Problem},
+    http_cache,
+    util::{self, Granularity},
+};
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        utoipa_actix_web::scope("/stats")
+            .service(dish_stats)
+            .service(price_stats),
+    );
+}
+
+/// Parse the date range of a statistics query, defaulting to the year up to today.
+fn date_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<(NaiveDate, NaiveDate), ApiError> {
+    let to = to.unwrap_or_else(|| chrono::Local::now().date_naive());
+    let from = from.unwrap_or(to - Duration::days(365));
+
+    if from > to {
+        return Err(ApiError::InvalidQuery(format!(
+            "The start of the date range ({from}) is after its end ({to})"
+        )));
+    }
+
+    Ok((from, to))
+}
+
+/// Parse the canteens of a statistics query, defaulting to all canteens.
+fn canteens(canteens: Option<&str>) -> Result<Vec<Canteen>, ApiError> {
+    Ok(canteens
+        .map(util::parse_canteens_comma_separated)
+        .transpose()?
+        .unwrap_or_else(|| Canteen::iter().collect())
+        .into_iter()
+        .sorted()
+        .dedup()
+        .collect())
+}
+
+fn round_days(days: f64) -> f64 {
+    (days * 10.0).round() / 10.0
+}
+
+fn round_price(price: Decimal) -> Decimal {
+    price.round_dp(2).normalize()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct DishStatsQuery {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    canteens: Option<String>,
+    limit: Option<u32>,
+    absent_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct DishStats {
+    /// First day of the evaluated date range.
+    from: NaiveDate,
+    /// Last day of the evaluated date range.
+    to: NaiveDate,
+    canteens: Vec<CanteenDishStats>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+#[schema(examples(json!({
+    "canteen": "forum",
+    "servings": 1204,
+    "distinct_dishes": 312,
+    "average_repeat_interval_days": 23.4,
+    "most_frequent": [
+        {
+            "id": "0b5a3e0c-5d0e-4a8b-9d4e-6f1c2a7b8c9d",
+            "name": "Bratwurst mit Currysauce und Pommes Frites",
+            "servings": 52,
+            "average_interval_days": 7.0
+        }
+    ],
+    "new_this_month": [
+        {
+            "id": "5e2f1a7c-3b4d-4e6f-8a9b-0c1d2e3f4a5b",
+            "name": "Kürbis-Risotto",
+            "first_served": "2026-10-14"
+        }
+    ],
+    "not_seen_recently": [
+        {
+            "id": "9c8b7a6d-5e4f-4a3b-2c1d-0e9f8a7b6c5d",
+            "name": "Spargelcremesuppe",
+            "servings": 12,
+            "last_served": "2026-06-24"
+        }
+    ]
+})))]
+struct CanteenDishStats {
+    canteen: Canteen,
+    /// Number of dishes served in the date range, counting each dish once per day.
+    servings: i64,
+    /// Number of different dishes served in the date range.
+    distinct_dishes: usize,
+    /// Average number of days between two servings of the same dish, over all dishes served
+    /// more than once.
+    average_repeat_interval_days: Option<f64>,
+    /// The dishes served on the most days, most frequent first.
+    most_frequent: Vec<DishFrequency>,
+    /// Dishes served for the first time at the canteen in the month the date range ends in,
+    /// newest first.
+    new_this_month: Vec<NewDish>,
+    /// Dishes served in the date range, but not in the last `absentDays` days of it, most
+    /// frequently served first.
+    not_seen_recently: Vec<AbsentDish>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct DishFrequency {
+    id: Uuid,
+    name: String,
+    /// Number of days the dish was served at the canteen.
+    servings: i64,
+    /// Average number of days between two servings at the canteen.
+    average_interval_days: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct NewDish {
+    id: Uuid,
+    name: String,
+    /// The first day the dish was served at the canteen.
+    first_served: NaiveDate,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct AbsentDish {
+    id: Uuid,
+    name: String,
+    /// Number of days the dish was served at the canteen in the date range.
+    servings: i64,
+    /// The last day the dish was served at the canteen.
+    last_served: NaiveDate,
+}
+
+/// Servings of a dish at a canteen in the date range of a query.
+#[derive(Debug, Clone)]
+struct DishServings {
+    id: Uuid,
+    name: String,
+    servings: i64,
+    first_served: NaiveDate,
+    last_served: NaiveDate,
+    /// The first day the dish was served at the canteen, also before the date range.
+    first_served_ever: NaiveDate,
+}
+
+impl DishServings {
+    /// Number of days between the first and the last serving in the date range.
+    fn span_days(&self) -> i64 {
+        (self.last_served - self.first_served).num_days()
+    }
+
+    fn average_interval_days(&self) -> Option<f64> {
+        (self.servings > 1)
+            .then(|| round_days(self.span_days() as f64 / (self.servings - 1) as f64))
+    }
+}
+
+#[utoipa::path(
+    summary = "Get dish statistics",
+    description = "Get statistics about the variety of the menus of canteen(s): the most frequently served dishes, how often dishes repeat, new dishes and dishes that have not been served for a long time.",
+    params(
+        ("from" = Option<NaiveDate>, Query, description = "First day of the date range to evaluate (defaults to a year before `to`)"),
+        ("to" = Option<NaiveDate>, Query, description = "Last day of the date range to evaluate (defaults to today)"),
+        ("canteens" = Option<String>, Query, description = "Comma-separated list of canteen identifiers to get statistics for (defaults to all canteens)", example = "forum,academica"),
+        ("limit" = Option<u32>, Query, description = "Maximum number of dishes in each list", minimum = 1, maximum = 100, example = 10),
+        ("absentDays" = Option<u32>, Query, description = "Number of days without a serving after which a dish counts as not seen recently (default: 90)", minimum = 1, example = 90),
+    ),
+    responses(
+        (status = OK, description = "Get dish statistics per canteen.", body = DishStats),
+        (status = NOT_MODIFIED, description = "The statistics have not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/dishes")]
+async fn dish_stats(
+    req: HttpRequest,
+    query: web::Query<DishStatsQuery>,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let (from, to) = date_range(query.from, query.to)?;
+    let canteens = canteens(query.canteens.as_deref())?;
+    let limit = query.limit.unwrap_or(10).clamp(1, 100) as usize;
+    let absent_since = to - Duration::days(query.absent_days.unwrap_or(90).max(1).into());
+    let month_start = to.with_day(1).expect("every month has a first day");
+
+    let mut servings_by_canteen = sqlx::query!(
+        r#"SELECT s.canteen, d.id, d.name, COUNT(*) AS "servings!", MIN(s.date) AS "first_served!", MAX(s.date) AS "last_served!",
+                (SELECT MIN(m.date) FROM meals m WHERE m.is_latest = TRUE AND m.canteen = s.canteen AND m.dish_id = s.dish_id) AS "first_served_ever!"
+            FROM (SELECT DISTINCT canteen, dish_id, date FROM meals WHERE is_latest = TRUE AND date BETWEEN $1 AND $2 AND canteen = ANY($3)) s
+            JOIN dishes d ON d.id = s.dish_id
+            GROUP BY s.canteen, s.dish_id, d.id, d.name"#,
+        from,
+        to,
+        &canteens
+            .iter()
+            .map(|c| c.get_identifier().to_string())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(db.as_ref())
+    .await?
+    .into_iter()
+    .map(|row| {
+        (
+            Canteen::from_str(&row.canteen).expect("Invalid database entry"),
+            DishServings {
+                id: row.id,
+                name: row.name,
+                servings: row.servings,
+                first_served: row.first_served,
+                last_served: row.last_served,
+                first_served_ever: row.first_served_ever,
+            },
+        )
+    })
+    .into_group_map();
+
+    let canteens = canteens
+        .into_iter()
+        .map(|canteen| {
+            let dishes = servings_by_canteen.remove(&canteen).unwrap_or_default();
+            canteen_dish_stats(canteen, dishes, limit, absent_since, month_start)
+        })
+        .collect();
+
+    Ok(http_cache::json_response(
+        &req,
+        &DishStats { from, to, canteens },
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
+}
+
+fn canteen_dish_stats(
+    canteen: Canteen,
+    dishes: Vec<DishServings>,
+    limit: usize,
+    absent_since: NaiveDate,
+    month_start: NaiveDate,
+) -> CanteenDishStats {
+    // the intervals between consecutive servings of a dish add up to the span of its servings
+    let (repeat_days, repeats) = dishes
+        .iter()
+        .filter(|dish| dish.servings > 1)
+        .fold((0, 0), |(days, repeats), dish| {
+            (days + dish.span_days(), repeats + dish.servings - 1)
+        });
+
+    let most_frequent = dishes
+        .iter()
+        .sorted_by(|a, b| {
+            b.servings
+                .cmp(&a.servings)
+                .then_with(|| a.name.cmp(&b.name))
+        })
+        .take(limit)
+        .map(|dish| DishFrequency {
+            id: dish.id,
+            name: dish.name.clone(),
+            servings: dish.servings,
+            average_interval_days: dish.average_interval_days(),
+        })
+        .collect();
+
+    let new_this_month = dishes
+        .iter()
+        .filter(|dish| dish.first_served_ever >= month_start)
+        .sorted_by(|a, b| {
+            b.first_served_ever
+                .cmp(&a.first_served_ever)
+                .then_with(|| a.name.cmp(&b.name))
+        })
+        .take(limit)
+        .map(|dish| NewDish {
+            id: dish.id,
+            name: dish.name.clone(),
+            first_served: dish.first_served_ever,
+        })
+        .collect();
+
+    let not_seen_recently = dishes
+        .iter()
+        .filter(|dish| dish.last_served <= absent_since)
+        .sorted_by(|a, b| {
+            b.servings
+                .cmp(&a.servings)
+                .then_with(|| a.name.cmp(&b.name))
+        })
+        .take(limit)
+        .map(|dish| AbsentDish {
+            id: dish.id,
+            name: dish.name.clone(),
+            servings: dish.servings,
+            last_served: dish.last_served,
+        })
+        .collect();
+
+    CanteenDishStats {
+        canteen,
+        servings: dishes.iter().map(|dish| dish.servings).sum(),
+        distinct_dishes: dishes.len(),
+        average_repeat_interval_days: (repeats > 0)
+            .then(|| round_days(repeat_days as f64 / repeats as f64)),
+        most_frequent,
+        new_this_month,
+        not_seen_recently,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct PriceStatsQuery {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    canteens: Option<String>,
+    #[serde(default)]
+    granularity: Granularity,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct PriceStats {
+    /// First day of the evaluated date range.
+    from: NaiveDate,
+    /// Last day of the evaluated date range.
+    to: NaiveDate,
+    granularity: Granularity,
+    canteens: Vec<CanteenPriceStats>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct CanteenPriceStats {
+    canteen: Canteen,
+    /// The periods any dish was served in, in chronological order.
+    periods: Vec<PeriodPrices>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+#[schema(examples(json!({
+    "start": "2026-10-01",
+    "servings": 214,
+    "students": { "average": "3.12", "min": "0.9", "median": "3.2", "max": "5.9", "index": "104.35" },
+    "employees": { "average": "4.71", "min": "1.3", "median": "4.8", "max": "8.1", "index": "104.12" },
+    "guests": { "average": "5.98", "min": "1.6", "median": "6.1", "max": "10.2", "index": "103.87" }
+})))]
+struct PeriodPrices {
+    /// First day of the period, which may be before the start of the date range.
+    start: NaiveDate,
+    /// Number of dishes served in the period.
+    servings: i64,
+    students: PriceSummary,
+    employees: PriceSummary,
+    guests: PriceSummary,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+struct PriceSummary {
+    average: Decimal,
+    min: Decimal,
+    median: Decimal,
+    max: Decimal,
+    /// Price of the basket of dishes served in the first period, relative to the first period
+    /// (which is 100). Only dishes of the basket served in both periods are compared.
+    index: Option<Decimal>,
+}
+
+/// Average prices of a dish in a period, for all price groups.
+type GroupPrices = [Decimal; 3];
+
+#[utoipa::path(
+    summary = "Get price statistics",
+    description = "Get the average, minimum, median and maximum prices of the dishes of canteen(s) per week or month and each price group, along with a price index of a fixed basket of dishes to track price changes over time.",
+    params(
+        ("from" = Option<NaiveDate>, Query, description = "First day of the date range to evaluate (defaults to a year before `to`)"),
+        ("to" = Option<NaiveDate>, Query, description = "Last day of the date range to evaluate (defaults to today)"),
+        ("canteens" = Option<String>, Query, description = "Comma-separated list of canteen identifiers to get statistics for (defaults to all canteens)", example = "forum,academica"),
+        ("granularity" = Option<Granularity>, Query, description = "Length of the periods prices are aggregated over (default: month)"),
+    ),
+    responses(
+        (status = OK, description = "Get price statistics per canteen and period.", body = PriceStats),
+        (status = NOT_MODIFIED, description = "The statistics have not changed since the version identified by `If-None-Match`."),
+        (status = BAD_REQUEST, description = "Invalid canteen identifier or query.", body = Problem, content_type = "application/problem+json"),
+        (status = INTERNAL_SERVER_ERROR, description = "Server failed to answer request.", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[get("/prices")]
+async fn price_stats(
+    req: HttpRequest,
+    query: web::Query<PriceStatsQuery>,
+    db: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let db = db.as_ref();
+    let (from, to) = date_range(query.from, query.to)?;
+    let canteens = canteens(query.canteens.as_deref())?;
+    let canteen_ids = canteens
+        .iter()
+        .map(|c| c.get_identifier().to_string())
+        .collect::<Vec<_>>();
+    let granularity = query.granularity;
+
+    let periods = sqlx::query!(
+        r#"SELECT canteen, date_trunc($3, date)::date AS "start!", COUNT(*) AS "servings!",
+                AVG(price_students) AS "students_average!", MIN(price_students) AS "students_min!", MAX(price_students) AS "students_max!",
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY price_students)::numeric AS "students_median!",
+                AVG(price_employees) AS "employees_average!", MIN(price_employees) AS "employees_min!", MAX(price_employees) AS "employees_max!",
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY price_employees)::numeric AS "employees_median!",
+                AVG(price_guests) AS "guests_average!", MIN(price_guests) AS "guests_min!", MAX(price_guests) AS "guests_max!",
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY price_guests)::numeric AS "guests_median!"
+            FROM meals WHERE is_latest = TRUE AND date BETWEEN $1 AND $2 AND canteen = ANY($4)
+            GROUP BY canteen, 2
+            ORDER BY canteen, 2"#,
+        from,
+        to,
+        granularity.date_trunc_field(),
+        &canteen_ids,
+    )
+    .fetch_all(db);
+
+    let dish_prices = sqlx::query!(
+        r#"SELECT canteen, dish_id, date_trunc($3, date)::date AS "start!",
+                AVG(price_students) AS "students!", AVG(price_employees) AS "employees!", AVG(price_guests) AS "guests!"
+            FROM meals WHERE is_latest = TRUE AND date BETWEEN $1 AND $2 AND canteen = ANY($4)
+            GROUP BY canteen, dish_id, 3"#,
+        from,
+        to,
+        granularity.date_trunc_field(),
+        &canteen_ids,
+    )
+    .fetch_all(db);
+
+    let (periods, dish_prices) = futures::try_join!(periods, dish_prices)?;
+
+    let mut indices = dish_prices
+        .into_iter()
+        .map(|row| {
+            (
+                Canteen::from_str(&row.canteen).expect("Invalid database entry"),
+                (
+                    row.start,
+                    row.dish_id,
+                    [row.students, row.employees, row.guests],
+                ),
+            )
+        })
+        .into_group_map()
+        .into_iter()
+        .map(|(canteen, prices)| (canteen, basket_price_index(prices)))
+        .collect::<HashMap<_, _>>();
+
+    let mut periods_by_canteen = periods
+        .into_iter()
+        .map(|row| {
+            let canteen = Canteen::from_str(&row.canteen).expect("Invalid database entry");
+            let index = indices
+                .get_mut(&canteen)
+                .and_then(|indices| indices.remove(&row.start))
+                .unwrap_or_default();
+            let summary = |average, min, median, max, index| PriceSummary {
+                average: round_price(average),
+                min: round_price(min),
+                median: round_price(median),
+                max: round_price(max),
+                index,
+            };
+
+            (
+                canteen,
+                PeriodPrices {
+                    start: row.start,
+                    servings: row.servings,
+                    students: summary(
+                        row.students_average,
+                        row.students_min,
+                        row.students_median,
+                        row.students_max,
+                        index[0],
+                    ),
+                    employees: summary(
+                        row.employees_average,
+                        row.employees_min,
+                        row.employees_median,
+                        row.employees_max,
+                        index[1],
+                    ),
+                    guests: summary(
+                        row.guests_average,
+                        row.guests_min,
+                        row.guests_median,
+                        row.guests_max,
+                        index[2],
+                    ),
+                },
+            )
+        })
+        .into_group_map();
+
+    let canteens = canteens
+        .into_iter()
+        .map(|canteen| CanteenPriceStats {
+            canteen,
+            periods: periods_by_canteen.remove(&canteen).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(http_cache::json_response(
+        &req,
+        &PriceStats {
+            from,
+            to,
+            granularity,
+            canteens,
+        },
+        None,
+        http_cache::DEFAULT_MAX_AGE,
+    ))
+}
+
+/// Compute the price index of each period for all price groups, given the average price of
+/// each dish in each period.
+///
+/// The basket consists of the dishes served in the first period. The index of a period is the
+/// price of the dishes of the basket served in it, relative to the price of the same dishes in
+/// the first period, so that changes in the menu do not affect the index.
+fn basket_price_index(
+    prices: Vec<(NaiveDate, Uuid, GroupPrices)>,
+) -> BTreeMap<NaiveDate, [Option<Decimal>; 3]> {
+    let by_period = prices
+        .into_iter()
+        .map(|(start, dish, prices)| (start, (dish, prices)))
+        .into_group_map()
+        .into_iter()
+        .map(|(start, prices)| (start, prices.into_iter().collect::<HashMap<_, _>>()))
+        .collect::<BTreeMap<_, _>>();
+
+    let Some(basket) = by_period.values().next() else {
+        return BTreeMap::new();
+    };
+
+    by_period
+        .iter()
+        .map(|(start, prices)| {
+            let index = std::array::from_fn(|group| {
+                let (current, base) = prices
+                    .iter()
+                    .filter_map(|(dish, prices)| Some((prices[group], basket.get(dish)?[group])))
+                    .fold(
+                        (Decimal::ZERO, Decimal::ZERO),
+                        |(current, base), (price, base_price)| (current + price, base + base_price),
+                    );
+
+                (!base.is_zero()).then(|| round_price(current / base * Decimal::ONE_HUNDRED))
+            });
+
+            (*start, index)
+        })
+        .collect()
+}
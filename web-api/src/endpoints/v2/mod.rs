@@ -0,0 +1,9 @@
+//! Next version of the API, which may still change until it is released.
+//!
+//! Endpoints changing the shape of their responses are added here, while v1 stays as it is.
+
+use utoipa_actix_web::service_config::ServiceConfig;
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(super::index);
+}
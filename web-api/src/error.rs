@@ -0,0 +1,239 @@
+use std::fmt;
+
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use utoipa::openapi::{path::Operation, response::ResponseBuilder, Content, OpenApi, Ref, RefOr};
+
+use crate::endpoints;
+
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Prefix of the `type` URI of all problems, followed by the error code.
+const PROBLEM_TYPE_PREFIX: &str = "urn:mensa-upb:problem:";
+
+/// Errors returned by the API, rendered as RFC 7807 `application/problem+json` responses.
+#[derive(Debug)]
+pub enum ApiError {
+    /// Some of the given canteen identifiers are unknown.
+    InvalidCanteen(Vec<String>),
+    /// A path segment could not be parsed.
+    InvalidPath(String),
+    /// The query string could not be parsed.
+    InvalidQuery(String),
+    /// No dish matches the request, with the names of similar dishes the client may have meant.
+    DishNotFound(Vec<String>),
+    RouteNotFound,
+    InvalidApiKey,
+    /// The rate limit was exceeded, the request may be retried after the given number of seconds.
+    RateLimited(u64),
+    Database(sqlx::Error),
+    Internal(&'static str),
+}
+
+impl ApiError {
+    /// Stable identifier of the kind of error, which clients can match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidCanteen(_) => "invalid-canteen",
+            Self::InvalidPath(_) => "invalid-path",
+            Self::InvalidQuery(_) => "invalid-query",
+            Self::DishNotFound(_) => "dish-not-found",
+            Self::RouteNotFound => "route-not-found",
+            Self::InvalidApiKey => "invalid-api-key",
+            Self::RateLimited(_) => "rate-limited",
+            Self::Database(_) => "database-error",
+            Self::Internal(_) => "internal-error",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::InvalidCanteen(_) => "Invalid canteen identifier",
+            Self::InvalidPath(_) => "Invalid path",
+            Self::InvalidQuery(_) => "Invalid query",
+            Self::DishNotFound(_) => "Dish not found",
+            Self::RouteNotFound => "Route not found",
+            Self::InvalidApiKey => "Invalid API key",
+            Self::RateLimited(_) => "Too many requests",
+            Self::Database(_) => "Failed to query database",
+            Self::Internal(_) => "Internal server error",
+        }
+    }
+
+    fn to_problem(&self) -> Problem {
+        let code = self.code();
+        Problem {
+            problem_type: format!("{PROBLEM_TYPE_PREFIX}{code}"),
+            code,
+            title: self.title(),
+            status: self.status_code().as_u16(),
+            detail: self.to_string(),
+            invalid: match self {
+                Self::InvalidCanteen(invalid) => Some(invalid.clone()),
+                _ => None,
+            },
+            suggestions: match self {
+                Self::DishNotFound(suggestions) if !suggestions.is_empty() => {
+                    Some(suggestions.clone())
+                }
+                _ => None,
+            },
+            retry_after: match self {
+                Self::RateLimited(after) => Some(*after),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCanteen(invalid) => {
+                write!(f, "Unknown canteen identifier(s): {}", invalid.join(", "))
+            }
+            Self::InvalidPath(err) | Self::InvalidQuery(err) => write!(f, "{err}"),
+            Self::DishNotFound(_) => write!(f, "No matching dish could be found"),
+            Self::RouteNotFound => write!(f, "No endpoint exists at this path"),
+            Self::InvalidApiKey => write!(f, "The given API key is unknown or revoked"),
+            Self::RateLimited(after) => {
+                write!(f, "Too many requests, try again after {after} seconds")
+            }
+            // do not leak details about the database to clients
+            Self::Database(_) => write!(f, "The database could not be queried"),
+            Self::Internal(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidCanteen(_) | Self::InvalidPath(_) | Self::InvalidQuery(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::DishNotFound(_) | Self::RouteNotFound => StatusCode::NOT_FOUND,
+            Self::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Database(_) | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::Database(err) => tracing::error!("Failed to query database: {err:?}"),
+            Self::Internal(msg) => tracing::error!("{msg}"),
+            _ => {}
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        response.content_type(PROBLEM_CONTENT_TYPE);
+        if let Self::RateLimited(after) = self {
+            response.insert_header((header::RETRY_AFTER, *after));
+        }
+        response.json(self.to_problem())
+    }
+}
+
+/// Description of an error as defined by RFC 7807.
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[schema(examples(json!({
+    "type": "urn:mensa-upb:problem:invalid-canteen",
+    "code": "invalid-canteen",
+    "title": "Invalid canteen identifier",
+    "status": 400,
+    "detail": "Unknown canteen identifier(s): invalid_canteen",
+    "invalid": ["invalid_canteen"]
+})))]
+pub struct Problem {
+    /// URI identifying the kind of error, `urn:mensa-upb:problem:` followed by the code.
+    #[serde(rename = "type")]
+    problem_type: String,
+    /// Stable identifier of the kind of error: `invalid-canteen`, `invalid-path`,
+    /// `invalid-query`, `dish-not-found`, `route-not-found`, `invalid-api-key`, `rate-limited`,
+    /// `database-error` or `internal-error`.
+    code: &'static str,
+    /// Short summary of the kind of error.
+    title: &'static str,
+    /// HTTP status code of the response.
+    status: u16,
+    /// Explanation of this occurrence of the error.
+    detail: String,
+    /// The invalid canteen identifiers, for `invalid-canteen` errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    invalid: Option<Vec<String>>,
+    /// Names of similar dishes, for `dish-not-found` errors about an unknown dish name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestions: Option<Vec<String>>,
+    /// Seconds after which the request may be retried, for `rate-limited` errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
+}
+
+/// Default service answering requests that match no route.
+pub async fn route_not_found() -> Result<HttpResponse, ApiError> {
+    Err(ApiError::RouteNotFound)
+}
+
+/// Document the errors every rate limited endpoint may return, which are not declared by the
+/// endpoints themselves.
+pub fn document_common_errors(mut api: OpenApi) -> OpenApi {
+    for (path, item) in api.paths.paths.iter_mut() {
+        // operational endpoints are not rate limited
+        if endpoints::is_operational(path) {
+            continue;
+        }
+
+        for operation in [
+            &mut item.get,
+            &mut item.put,
+            &mut item.post,
+            &mut item.delete,
+            &mut item.patch,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            add_problem_response(
+                operation,
+                StatusCode::UNAUTHORIZED,
+                "The API key given in the `Authorization` header is invalid.",
+            );
+            add_problem_response(
+                operation,
+                StatusCode::TOO_MANY_REQUESTS,
+                "The rate limit was exceeded, see the `Retry-After` header.",
+            );
+        }
+    }
+
+    api
+}
+
+fn add_problem_response(operation: &mut Operation, status: StatusCode, description: &str) {
+    operation
+        .responses
+        .responses
+        .entry(status.as_u16().to_string())
+        .or_insert_with(|| {
+            RefOr::T(
+                ResponseBuilder::new()
+                    .description(description)
+                    .content(
+                        PROBLEM_CONTENT_TYPE,
+                        Content::new(Some(Ref::from_schema_name("Problem"))),
+                    )
+                    .build(),
+            )
+        });
+}
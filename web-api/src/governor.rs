@@ -1,92 +1,430 @@
-use std::{net::IpAddr, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use actix_governor::{
-    governor::{
-        clock::{Clock, DefaultClock, QuantaInstant},
-        middleware::NoOpMiddleware,
-    },
-    GovernorConfig, GovernorConfigBuilder, KeyExtractor, SimpleKeyExtractionError,
+use ::governor::{
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
+    DefaultKeyedRateLimiter, Quota,
 };
 use actix_web::{
-    dev::ServiceRequest,
-    http::{header::ContentType, StatusCode},
-    HttpResponse, HttpResponseBuilder,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderMap, HeaderName},
+        StatusCode,
+    },
+    middleware::Next,
+    web, HttpResponse, ResponseError as _,
 };
-use serde::{Deserialize, Serialize};
+use shared::ApiKeyTier;
+use sqlx::{types::Uuid, PgPool};
+
+use crate::{api_keys::ApiKeyStore, client_ip, endpoints, error::ApiError, http_cache, metrics};
+
+/// Conditional requests answered with `304 Not Modified` are cheap, so their quota is this many
+/// times larger and replenishes this many times faster than the regular one. Conditional requests
+/// answered in full also count against the regular quota.
+const CONDITIONAL_QUOTA_FACTOR: u32 = 5;
+/// Autocompletion sends a request per keystroke and is answered from a cache, so its quota is
+/// this many times larger and replenishes this many times faster than the regular one.
+const AUTOCOMPLETE_QUOTA_FACTOR: u32 = 10;
+/// Interval in which the state of clients that have not been limited recently is removed.
+const CLEAN_UP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Where the state of the rate limits is stored.
+#[derive(Debug, Clone)]
+pub enum RateLimitBackend {
+    /// In process memory, so every replica of the API limits requests on its own.
+    Memory,
+    /// In the database, so the limits hold across all replicas of the API.
+    Postgres(PgPool),
+}
+
+/// Limits all requests, each against the quota of its class: anonymous requests per client IP
+/// address (with conditional requests and autocompletion limited separately) and requests with an
+/// API key per key, according to its tier.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    api_keys: ApiKeyStore,
+    /// Clients whose regular quota is exhausted, until it allows a request again. Their
+    /// conditional requests are rejected up front, as they may be answered in full.
+    exhausted: Arc<Mutex<HashMap<RateLimitKey, Instant>>>,
+    anonymous: Arc<RateLimit>,
+    anonymous_conditional: Arc<RateLimit>,
+    anonymous_autocomplete: Arc<RateLimit>,
+    basic: Arc<RateLimit>,
+    extended: Arc<RateLimit>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        seconds_replenish: u64,
+        burst_size: u32,
+        api_keys: ApiKeyStore,
+        backend: RateLimitBackend,
+    ) -> Self {
+        let period = Duration::from_secs(seconds_replenish);
+        let limit =
+            |name, period, burst_size| Arc::new(RateLimit::new(name, period, burst_size, &backend));
+
+        Self {
+            api_keys,
+            exhausted: Arc::default(),
+            anonymous: limit("anonymous", period, burst_size),
+            anonymous_conditional: limit(
+                "conditional",
+                period / CONDITIONAL_QUOTA_FACTOR,
+                burst_size.saturating_mul(CONDITIONAL_QUOTA_FACTOR),
+            ),
+            anonymous_autocomplete: limit(
+                "autocomplete",
+                period / AUTOCOMPLETE_QUOTA_FACTOR,
+                burst_size.saturating_mul(AUTOCOMPLETE_QUOTA_FACTOR),
+            ),
+            basic: limit("basic", Duration::from_secs(1), 60),
+            extended: limit("extended", Duration::from_millis(100), 600),
+        }
+    }
+
+    /// Remove the state of clients whose quota is fully replenished.
+    async fn clean_up(&self) -> sqlx::Result<()> {
+        let now = Instant::now();
+        self.exhausted
+            .lock()
+            .expect("rate limiter lock poisoned")
+            .retain(|_, until| *until > now);
+
+        for limit in [
+            &self.anonymous,
+            &self.anonymous_conditional,
+            &self.anonymous_autocomplete,
+            &self.basic,
+            &self.extended,
+        ] {
+            match &limit.state {
+                RateLimitState::Memory(limiter) => {
+                    limiter.retain_recent();
+                    limiter.shrink_to_fit();
+                }
+                RateLimitState::Postgres(db) => {
+                    // the buckets of all quotas are stored in the same table
+                    sqlx::query!("DELETE FROM rate_limit_buckets WHERE full_at < NOW()")
+                        .execute(db)
+                        .await?;
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the quota a request counts against and the key it is limited by, or `None` if the
+    /// request is not limited.
+    fn classify(&self, req: &ServiceRequest) -> Result<Option<Classification<'_>>, ApiError> {
+        // health checks and metrics are probed frequently by the orchestrator
+        if endpoints::is_operational(req.path()) {
+            return Ok(None);
+        }
+
+        let api_key = self
+            .api_keys
+            .authenticate(req.headers())
+            .map_err(|_| ApiError::InvalidApiKey)?;
+
+        let peer_addr = req.peer_addr().ok_or(ApiError::Internal(
+            "Could not extract peer IP address from request",
+        ))?;
+        let ip = client_ip::client_ip(peer_addr.ip(), req.headers());
 
-use crate::USE_X_FORWARDED_HOST;
+        if client_ip::is_rate_limit_exempt(ip) {
+            return Ok(None);
+        }
+
+        let (limit, key) = match api_key {
+            Some(api_key) => match api_key.tier {
+                ApiKeyTier::Basic => (&*self.basic, RateLimitKey::ApiKey(api_key.id)),
+                ApiKeyTier::Extended => (&*self.extended, RateLimitKey::ApiKey(api_key.id)),
+                ApiKeyTier::Unlimited => return Ok(None),
+            },
+            None => {
+                let key = RateLimitKey::Client(client_ip::rate_limit_address(ip));
+                if endpoints::is_autocomplete(req.path()) {
+                    (&*self.anonymous_autocomplete, key)
+                } else if http_cache::is_conditional_request(req.headers()) {
+                    // the validators may be bogus, so only a `304 Not Modified` is cheap
+                    return Ok(Some(Classification {
+                        limit: &self.anonymous_conditional,
+                        key,
+                        full_response_limit: Some(&self.anonymous),
+                    }));
+                } else {
+                    (&*self.anonymous, key)
+                }
+            }
+        };
+
+        Ok(Some(Classification {
+            limit,
+            key,
+            full_response_limit: None,
+        }))
+    }
 
-pub fn get_governor(
-    seconds_replenish: u64,
+    /// Get the time until the regular quota of a client allows a request again, if it is
+    /// exhausted.
+    fn exhausted_for(&self, key: RateLimitKey) -> Option<Duration> {
+        let exhausted = self.exhausted.lock().expect("rate limiter lock poisoned");
+        exhausted
+            .get(&key)
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+            .filter(|wait_time| !wait_time.is_zero())
+    }
+
+    fn mark_exhausted(&self, key: RateLimitKey, wait_time: Duration) {
+        self.exhausted
+            .lock()
+            .expect("rate limiter lock poisoned")
+            .insert(key, Instant::now() + wait_time);
+    }
+}
+
+/// The quota a request counts against.
+#[derive(Debug, Clone, Copy)]
+struct Classification<'a> {
+    limit: &'a RateLimit,
+    key: RateLimitKey,
+    /// The quota the request additionally counts against if it is not answered with
+    /// `304 Not Modified`.
+    full_response_limit: Option<&'a RateLimit>,
+}
+
+/// The quota of a class of requests and the state of all keys limited by it.
+#[derive(Debug)]
+struct RateLimit {
+    /// Name of the quota, distinguishing its buckets in the database.
+    name: &'static str,
+    /// Time after which a single request is replenished.
+    period: Duration,
     burst_size: u32,
-) -> GovernorConfig<UserToken, NoOpMiddleware<QuantaInstant>> {
-    GovernorConfigBuilder::default()
-        .seconds_per_request(seconds_replenish)
-        .burst_size(burst_size)
-        .key_extractor(UserToken)
-        .finish()
-        .unwrap()
+    state: RateLimitState,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
-pub struct UserToken;
+#[derive(Debug)]
+enum RateLimitState {
+    Memory(DefaultKeyedRateLimiter<RateLimitKey, StateInformationMiddleware>),
+    Postgres(PgPool),
+}
 
-impl KeyExtractor for UserToken {
-    type Key = IpAddr;
-    type KeyExtractionError = SimpleKeyExtractionError<&'static str>;
+/// The result of counting a request against its quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RateLimitDecision {
+    /// Time until the next request is allowed, if this one exceeds the quota.
+    retry_after: Option<Duration>,
+    remaining: u32,
+    /// Time until the whole burst is available again.
+    reset: Duration,
+}
+
+impl RateLimit {
+    fn new(
+        name: &'static str,
+        period: Duration,
+        burst_size: u32,
+        backend: &RateLimitBackend,
+    ) -> Self {
+        let period = period.max(Duration::from_millis(1));
+        let burst_size = NonZeroU32::new(burst_size).unwrap_or(NonZeroU32::MIN);
+        let state = match backend {
+            RateLimitBackend::Memory => {
+                let quota = Quota::with_period(period)
+                    .expect("period is not zero")
+                    .allow_burst(burst_size);
+                RateLimitState::Memory(DefaultKeyedRateLimiter::keyed(quota).with_middleware())
+            }
+            RateLimitBackend::Postgres(db) => RateLimitState::Postgres(db.clone()),
+        };
 
-    fn name(&self) -> &'static str {
-        "Bearer token"
+        Self {
+            name,
+            period,
+            burst_size: burst_size.get(),
+            state,
+        }
     }
 
-    fn extract(&self, req: &ServiceRequest) -> Result<Self::Key, Self::KeyExtractionError> {
-        let mut ip = USE_X_FORWARDED_HOST
-            .then(|| {
-                req.headers()
-                    .get("X-Forwarded-Host")
-                    .and_then(|header| IpAddr::from_str(header.to_str().unwrap_or_default()).ok())
-            })
-            .flatten()
-            .or_else(|| req.peer_addr().map(|socket| socket.ip()))
-            .ok_or_else(|| {
-                Self::KeyExtractionError::new(
-                    r#"{ "code": 500, "msg": "Could not extract peer IP address from request"}"#,
+    /// Count a request against the quota of the given key.
+    async fn check(&self, key: RateLimitKey) -> sqlx::Result<RateLimitDecision> {
+        match &self.state {
+            RateLimitState::Memory(limiter) => Ok(match limiter.check_key(&key) {
+                Ok(snapshot) => {
+                    let remaining = snapshot.remaining_burst_capacity();
+                    RateLimitDecision {
+                        retry_after: None,
+                        remaining,
+                        reset: self.period * (self.burst_size - remaining),
+                    }
+                }
+                Err(negative) => {
+                    let wait_time = negative.wait_time_from(DefaultClock::default().now());
+                    RateLimitDecision {
+                        retry_after: Some(wait_time),
+                        remaining: 0,
+                        reset: wait_time + self.period * (self.burst_size - 1),
+                    }
+                }
+            }),
+            RateLimitState::Postgres(db) => {
+                let bucket = sqlx::query!(
+                    r#"SELECT allowed AS "allowed!", remaining AS "remaining!" FROM take_rate_limit_token($1, $2, $3)"#,
+                    format!("{}:{key}", self.name),
+                    self.burst_size as i32,
+                    self.period.as_secs_f64(),
                 )
-                .set_content_type(ContentType::json())
-                .set_status_code(StatusCode::INTERNAL_SERVER_ERROR)
-            })?;
-
-        // customers often get their own /56 prefix, apply rate-limiting per prefix instead of per
-        // address for IPv6
-        if let IpAddr::V6(ipv6) = ip {
-            let mut octets = ipv6.octets();
-            octets[7..16].fill(0);
-            ip = IpAddr::V6(octets.into());
+                .fetch_one(db)
+                .await?;
+
+                let missing = f64::from(self.burst_size) - bucket.remaining;
+                Ok(RateLimitDecision {
+                    retry_after: (!bucket.allowed)
+                        .then(|| self.period.mul_f64(1.0 - bucket.remaining)),
+                    remaining: bucket.remaining.floor() as u32,
+                    reset: self.period.mul_f64(missing.max(0.0)),
+                })
+            }
         }
-        Ok(ip)
     }
 
-    fn exceed_rate_limit_response(
-        &self,
-        negative: &actix_governor::governor::NotUntil<QuantaInstant>,
-        mut response: HttpResponseBuilder,
-    ) -> HttpResponse {
-        let wait_time = negative
-            .wait_time_from(DefaultClock::default().now())
-            .as_secs();
-        response.content_type(ContentType::json())
-            .body(
-                format!(
-                    r#"{{"code":429, "error": "TooManyRequests", "message": "Too many requests, try again after {wait_time} seconds", "after": {wait_time}}}"#
-                )
-            )
+    fn insert_headers(&self, headers: &mut HeaderMap, decision: RateLimitDecision) {
+        headers.insert(
+            HeaderName::from_static("ratelimit-limit"),
+            self.burst_size.into(),
+        );
+        headers.insert(
+            HeaderName::from_static("ratelimit-remaining"),
+            decision.remaining.into(),
+        );
+        headers.insert(
+            HeaderName::from_static("ratelimit-reset"),
+            ceil_secs(decision.reset).into(),
+        );
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RateLimitKey {
+    Client(IpAddr),
+    ApiKey(Uuid),
+}
 
-    fn whitelisted_keys(&self) -> Vec<Self::Key> {
-        Vec::new()
+impl fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Client(ip) => write!(f, "{ip}"),
+            Self::ApiKey(id) => write!(f, "{id}"),
+        }
     }
+}
+
+/// Middleware applying the [`RateLimiter`] registered as app data.
+///
+/// Adds `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers to all limited
+/// responses, as well as `Retry-After` if the quota is exceeded.
+pub async fn rate_limit(
+    limiter: web::Data<RateLimiter>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let Classification {
+        limit,
+        key,
+        full_response_limit,
+    } = match limiter.classify(&req) {
+        Ok(Some(classification)) => classification,
+        Ok(None) => return Ok(next.call(req).await?.map_into_left_body()),
+        Err(err) => {
+            return Ok(req
+                .into_response(err.error_response())
+                .map_into_right_body())
+        }
+    };
+
+    // clients over their regular quota could otherwise keep triggering full responses
+    let exhausted = full_response_limit
+        .and_then(|full_response_limit| Some((full_response_limit, limiter.exhausted_for(key)?)));
+    if let Some((full_response_limit, wait_time)) = exhausted {
+        let response = rejection(full_response_limit, key, wait_time);
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    let decision = match limit.check(key).await {
+        Ok(decision) => decision,
+        Err(err) => {
+            // rather serve requests without limits than not at all
+            tracing::error!("Failed to check rate limit: {err}");
+            return Ok(next.call(req).await?.map_into_left_body());
+        }
+    };
 
-    fn key_name(&self, _key: &Self::Key) -> Option<String> {
-        None
+    if let Some(retry_after) = decision.retry_after {
+        let mut response = rejection(limit, key, retry_after);
+        limit.insert_headers(response.headers_mut(), decision);
+        return Ok(req.into_response(response).map_into_right_body());
     }
+
+    let mut res = next.call(req).await?;
+
+    let (limit, decision) = match full_response_limit {
+        Some(full_response_limit) if res.status() != StatusCode::NOT_MODIFIED => {
+            match full_response_limit.check(key).await {
+                Ok(decision) => (full_response_limit, decision),
+                Err(err) => {
+                    tracing::error!("Failed to check rate limit: {err}");
+                    (limit, decision)
+                }
+            }
+        }
+        _ => (limit, decision),
+    };
+
+    if let Some(retry_after) = decision.retry_after {
+        limiter.mark_exhausted(key, retry_after);
+
+        let mut response = rejection(limit, key, retry_after);
+        limit.insert_headers(response.headers_mut(), decision);
+        return Ok(res.into_response(response).map_into_right_body());
+    }
+
+    limit.insert_headers(res.headers_mut(), decision);
+    Ok(res.map_into_left_body())
+}
+
+/// Build the response to a request exceeding the given quota.
+fn rejection(limit: &RateLimit, key: RateLimitKey, retry_after: Duration) -> HttpResponse {
+    metrics::record_rate_limit_rejection(limit.name);
+    if let RateLimitKey::ApiKey(id) = key {
+        tracing::info!("Rate limit exceeded for API key {id}");
+    }
+
+    ApiError::RateLimited(ceil_secs(retry_after)).error_response()
+}
+
+/// Periodically remove the state of clients that have not been limited recently.
+pub async fn clean_up_rate_limits(limiter: RateLimiter) {
+    loop {
+        tokio::time::sleep(CLEAN_UP_INTERVAL).await;
+        if let Err(err) = limiter.clean_up().await {
+            tracing::error!("Failed to clean up rate limits: {err}");
+        }
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
 }
@@ -0,0 +1,136 @@
+use std::time::SystemTime;
+
+use actix_web::{
+    http::header::{
+        CacheControl, CacheDirective, ContentType, ETag, EntityTag, Header as _, HeaderMap,
+        HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+    },
+    HttpRequest, HttpResponse, ResponseError as _,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::error::ApiError;
+
+/// Freshness lifetime for responses that do not depend on a specific date.
+pub const DEFAULT_MAX_AGE: u32 = 60 * 60;
+/// Freshness lifetime for responses that never change.
+const IMMUTABLE_MAX_AGE: u32 = 365 * 24 * 60 * 60;
+
+/// Returns whether the request revalidates a previously cached response.
+pub fn is_conditional_request(headers: &HeaderMap) -> bool {
+    headers.contains_key(IfNoneMatch::name()) || headers.contains_key(IfModifiedSince::name())
+}
+
+/// Get the number of seconds a menu for the given date may be cached by clients.
+///
+/// Menus in the past are not refreshed anymore, menus of today change at most a few times a day
+/// and upcoming menus are only refreshed every couple of days.
+pub fn max_age_for_date(date: NaiveDate) -> u32 {
+    let today = chrono::Local::now().date_naive();
+    let days = (date - today).num_days();
+
+    match days {
+        ..-1 => 7 * 24 * 60 * 60,
+        -1 => 60 * 60,
+        0 => 5 * 60,
+        1..=31 => 60 * 60,
+        _ => 24 * 60 * 60,
+    }
+}
+
+/// Build a JSON response carrying an `ETag` computed from the serialized body, answering with
+/// `304 Not Modified` if the request's `If-None-Match` or `If-Modified-Since` header matches.
+pub fn json_response<T: Serialize>(
+    req: &HttpRequest,
+    value: &T,
+    last_modified: Option<DateTime<Utc>>,
+    max_age: u32,
+) -> HttpResponse {
+    build_json_response(
+        req,
+        value,
+        last_modified,
+        vec![CacheDirective::Public, CacheDirective::MaxAge(max_age)],
+    )
+}
+
+/// Serialize a value that never changes (e.g. a past version of a menu) as JSON, to be cached
+/// for a year without revalidation.
+pub fn immutable_json_response<T: Serialize>(req: &HttpRequest, value: &T) -> HttpResponse {
+    build_json_response(
+        req,
+        value,
+        None,
+        vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(IMMUTABLE_MAX_AGE),
+            CacheDirective::Extension("immutable".to_string(), None),
+        ],
+    )
+}
+
+fn build_json_response<T: Serialize>(
+    req: &HttpRequest,
+    value: &T,
+    last_modified: Option<DateTime<Utc>>,
+    cache_control: Vec<CacheDirective>,
+) -> HttpResponse {
+    let body = match serde_json::to_vec(value) {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::error!("Failed to serialize response: {err}");
+            return ApiError::Internal("Failed to serialize response").error_response();
+        }
+    };
+
+    let etag = EntityTag::new_strong(content_hash(&body));
+    let last_modified = last_modified.map(|dt| HttpDate::from(SystemTime::from(dt)));
+
+    let not_modified = is_not_modified(req, &etag, last_modified);
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+
+    response
+        .insert_header(ETag(etag))
+        .insert_header(CacheControl(cache_control));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(last_modified));
+    }
+
+    if not_modified {
+        response.finish()
+    } else {
+        response.content_type(ContentType::json()).body(body)
+    }
+}
+
+fn is_not_modified(req: &HttpRequest, etag: &EntityTag, last_modified: Option<HttpDate>) -> bool {
+    // If-None-Match takes precedence over If-Modified-Since (RFC 9110, section 13.2.2)
+    if req.headers().contains_key(IfNoneMatch::name()) {
+        return match IfNoneMatch::parse(req) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+            Err(_) => false,
+        };
+    }
+
+    match (IfModifiedSince::parse(req), last_modified) {
+        (Ok(IfModifiedSince(since)), Some(last_modified)) => {
+            SystemTime::from(last_modified) <= SystemTime::from(since)
+        }
+        _ => false,
+    }
+}
+
+fn content_hash(body: &[u8]) -> String {
+    Sha256::digest(body)
+        .iter()
+        .take(16)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
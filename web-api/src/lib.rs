@@ -1,17 +1,37 @@
+mod api_keys;
+mod client_ip;
+mod deprecation;
 mod dish;
+mod dish_search;
 pub mod endpoints;
+mod error;
 mod governor;
+mod http_cache;
 mod menu;
+mod menu_cache;
+mod menu_history;
+mod menu_stream;
+mod metrics;
+mod notifier;
+mod suggestion_cache;
+mod telemetry;
+mod upcoming_menus;
 mod util;
+mod webhook_worker;
 
-use std::sync::LazyLock;
+use sqlx::migrate::Migrator;
 
+pub use api_keys::{reload_api_keys, ApiKeyStore};
 pub use dish::{Dish, DishPrices};
-pub use governor::get_governor;
+pub use error::{document_common_errors, route_not_found, ApiError};
+pub use governor::{clean_up_rate_limits, rate_limit, RateLimitBackend, RateLimiter};
 pub use menu::Menu;
+pub use menu_cache::MenuCache;
+pub use metrics::{install_recorder, record_requests, run_metrics_upkeep};
+pub use notifier::{MenuChangeNotifier, MenuEvent, MenuEventReceiver};
+pub use suggestion_cache::SuggestionCache;
+pub use telemetry::trace_requests;
+pub use upcoming_menus::UpcomingMenuRefresher;
+pub use webhook_worker::deliver_webhooks;
 
-pub(crate) static USE_X_FORWARDED_HOST: LazyLock<bool> = LazyLock::new(|| {
-    std::env::var("API_USE_X_FORWARDED_HOST")
-        .map(|val| val == "true")
-        .unwrap_or(false)
-});
+pub static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
@@ -1,16 +1,25 @@
-use std::env;
+use std::{env, time::Duration};
 
 use actix_cors::Cors;
-use actix_governor::Governor;
 use actix_web::{
-    middleware::{self, NormalizePath},
+    middleware::{self, from_fn, NormalizePath},
     web, App, HttpServer,
 };
 use anyhow::Result;
-use itertools::Itertools;
-use mensa_upb_api::get_governor;
+use clap::Parser as _;
+use mensa_upb_api::{
+    clean_up_rate_limits, deliver_webhooks, document_common_errors,
+    endpoints::{self, API_VERSIONS},
+    install_recorder, rate_limit, record_requests, reload_api_keys, route_not_found,
+    run_metrics_upkeep, trace_requests, ApiError, ApiKeyStore, MenuCache, MenuChangeNotifier,
+    RateLimitBackend, RateLimiter, SuggestionCache, UpcomingMenuRefresher, MIGRATOR,
+};
+use mensa_upb_scraper::{
+    config::{self, ApiOverrides, Config, ConfigArgs, Overrides},
+    telemetry,
+};
 use sqlx::postgres::PgPoolOptions;
-use tracing::{debug, error, info, level_filters::LevelFilter};
+use tracing::{debug, error, info, level_filters::LevelFilter, warn};
 use tracing_subscriber::EnvFilter;
 use utoipa::OpenApi as _;
 use utoipa_actix_web::AppExt as _;
@@ -20,56 +29,106 @@ use utoipa_rapidoc::RapiDoc;
 #[openapi(info(title = "Mensa UPB API"))]
 struct ApiDoc;
 
+/// Serve the menus of the canteens of the University of Paderborn
+#[derive(Debug, Clone, clap::Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(flatten)]
+    api: ApiOverrides,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    // load the .env file before the configuration, as it may contain environment variables
+    let dotenv = dotenvy::dotenv();
+
+    let cli = Cli::parse();
+    let config = Config::load(
+        &cli.config,
+        Overrides {
+            api: cli.api,
+            ..Default::default()
+        },
+    )?;
+    if cli.config.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+    let config = config::init(config);
+
     let env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::WARN.into())
         .from_env()
         .expect("Invalid filter")
         .add_directive("mensa_upb_api=debug".parse().unwrap())
         .add_directive("mensa_upb_scraper=debug".parse().unwrap());
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+    let _telemetry = telemetry::init_tracing(
+        "mensa-upb-api",
+        env_filter,
+        config.telemetry.otlp_endpoint.as_deref(),
+    )?;
 
-    match dotenvy::dotenv() {
+    match dotenv {
         Ok(_) => debug!("Loaded .env file"),
         Err(dotenvy::Error::LineParse(..)) => error!("Malformed .env file"),
         Err(_) => {}
     }
+    if env::var_os("API_USE_X_FORWARDED_HOST").is_some() {
+        warn!("API_USE_X_FORWARDED_HOST is no longer supported, use API_TRUSTED_PROXIES");
+    }
+
+    let metrics_handle = install_recorder()?;
+    tokio::spawn(run_metrics_upkeep(metrics_handle.clone()));
+    let metrics_handle = web::Data::new(metrics_handle);
+
+    let db = PgPoolOptions::new().connect_lazy(&config.database_url)?;
+
+    MIGRATOR.run(&db).await?;
 
-    let db = PgPoolOptions::new()
-        .connect_lazy(&env::var("DATABASE_URL").expect("missing DATABASE_URL env variable"))?;
-
-    sqlx::migrate!("../migrations").run(&db).await?;
-
-    let interface = env::var("API_INTERFACE").unwrap_or("127.0.0.1".to_string());
-    let port = env::var("API_PORT")
-        .ok()
-        .and_then(|p| p.parse::<u16>().ok())
-        .unwrap_or(8080);
-    let seconds_replenish = env::var("API_RATE_LIMIT_SECONDS")
-        .ok()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(5);
-    let burst_size = env::var("API_RATE_LIMIT_BURST")
-        .ok()
-        .and_then(|s| s.parse::<u32>().ok())
-        .unwrap_or(20);
-
-    let allowed_cors = env::var("API_CORS_ALLOWED")
-        .map(|val| {
-            val.split(',')
-                .map(|domain| domain.trim().to_string())
-                .collect_vec()
-        })
-        .ok()
-        .unwrap_or_default();
-
-    let governor_conf = get_governor(seconds_replenish, burst_size);
-
-    info!("Starting server on {}:{}", interface, port);
+    let api_config = &config.api;
+
+    let api_keys = ApiKeyStore::default();
+    api_keys.reload(&db).await?;
+    tokio::spawn(reload_api_keys(db.clone(), api_keys.clone()));
+
+    let rate_limit_backend = match api_config.rate_limit.backend {
+        config::RateLimitBackend::Memory => RateLimitBackend::Memory,
+        config::RateLimitBackend::Postgres => RateLimitBackend::Postgres(db.clone()),
+    };
+    let rate_limiter = web::Data::new(RateLimiter::new(
+        api_config.rate_limit.seconds,
+        api_config.rate_limit.burst,
+        api_keys,
+        rate_limit_backend,
+    ));
+    tokio::spawn(clean_up_rate_limits(rate_limiter.get_ref().clone()));
+
+    let menu_cache = web::Data::new(MenuCache::new(
+        api_config.menu_cache_size,
+        Duration::from_secs(api_config.menu_cache_ttl_seconds),
+    ));
+
+    let suggestion_cache = web::Data::new(SuggestionCache::new());
+    let upcoming_menu_refresher = web::Data::new(UpcomingMenuRefresher::new());
+
+    let menu_change_notifier =
+        web::Data::new(MenuChangeNotifier::new(menu_cache.clone().into_inner()));
+    tokio::spawn(deliver_webhooks(
+        db.clone(),
+        menu_change_notifier.subscribe(),
+    ));
+    tokio::spawn(menu_change_notifier.clone().into_inner().listen(db.clone()));
+
+    info!(
+        "Starting server on {}:{}",
+        api_config.interface, api_config.port
+    );
 
     HttpServer::new(move || {
-        let cors = allowed_cors
+        let cors = api_config
+            .cors_allowed
             .iter()
             .fold(Cors::default(), |cors, domain| {
                 if domain == "*" {
@@ -81,21 +140,66 @@ async fn main() -> Result<()> {
             .send_wildcard()
             .allow_any_method()
             .allow_any_header()
+            .expose_headers([
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "RateLimit-Reset",
+                "Retry-After",
+                "X-Request-Id",
+                "Deprecation",
+                "Sunset",
+                "Link",
+            ])
             .max_age(3600);
-        App::new()
-            .wrap(NormalizePath::new(middleware::TrailingSlash::Trim))
-            .wrap(Governor::new(&governor_conf))
+        let (app, api) = App::new()
+            .wrap(from_fn(rate_limit))
+            .wrap(from_fn(record_requests))
             .wrap(cors)
+            .wrap(from_fn(trace_requests))
+            // outermost, so that all other middleware sees the normalized path
+            .wrap(NormalizePath::new(middleware::TrailingSlash::Trim))
             .app_data(web::Data::new(db.clone()))
+            .app_data(menu_cache.clone())
+            .app_data(suggestion_cache.clone())
+            .app_data(upcoming_menu_refresher.clone())
+            .app_data(menu_change_notifier.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(metrics_handle.clone())
+            .app_data(
+                web::PathConfig::default()
+                    .error_handler(|err, _| ApiError::InvalidPath(err.to_string()).into()),
+            )
+            .app_data(
+                web::QueryConfig::default()
+                    .error_handler(|err, _| ApiError::InvalidQuery(err.to_string()).into()),
+            )
             .into_utoipa_app()
             .openapi(ApiDoc::openapi())
-            .configure(mensa_upb_api::endpoints::configure)
-            .openapi_service(|api| {
-                RapiDoc::with_openapi("/api-docs/openapi.json", api).path("/rapidoc")
-            })
-            .into_app()
+            .configure(endpoints::configure)
+            .split_for_parts();
+
+        let app = API_VERSIONS.into_iter().fold(app, |app, version| {
+            app.service(
+                RapiDoc::with_openapi(
+                    format!("/api-docs/{version}/openapi.json"),
+                    document_common_errors(endpoints::openapi_for_version(&api, version)),
+                )
+                .path(format!("/rapidoc/{version}")),
+            )
+        });
+
+        // the documentation of v1 was served here before the API was versioned
+        app.service(
+            RapiDoc::with_openapi(
+                "/api-docs/openapi.json",
+                document_common_errors(endpoints::openapi_for_version(&api, "v1")),
+            )
+            .path("/rapidoc"),
+        )
+        .configure(endpoints::configure_unversioned)
+        .default_service(web::to(route_not_found))
     })
-    .bind((interface.as_str(), port))?
+    .bind((api_config.interface.as_str(), api_config.port))?
     .run()
     .await?;
 
@@ -1,5 +1,6 @@
-use chrono::NaiveDate;
-use mensa_upb_scraper::check_refresh;
+use chrono::{DateTime, NaiveDate, Utc};
+use mensa_upb_scraper::{check_refresh, config};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use shared::{Canteen, DishType};
 use sqlx::PgPool;
@@ -7,6 +8,34 @@ use std::str::FromStr as _;
 
 use crate::{Dish, DishPrices};
 
+/// A dish of a menu, with the canteens serving it aggregated.
+struct MenuRow {
+    name: String,
+    canteens: Vec<String>,
+    dish_type: DishType,
+    image_src: Option<String>,
+    price_students: Decimal,
+    price_employees: Decimal,
+    price_guests: Decimal,
+    vegan: bool,
+    vegetarian: bool,
+}
+
+/// A meal of a menu as stored by one scrape, valid until it was replaced by a later scrape.
+pub(crate) struct MealVersion {
+    name: String,
+    canteen: String,
+    dish_type: DishType,
+    image_src: Option<String>,
+    price_students: Decimal,
+    price_employees: Decimal,
+    price_guests: Decimal,
+    vegan: bool,
+    vegetarian: bool,
+    pub refreshed_at: DateTime<Utc>,
+    pub replaced_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default, utoipa::ToSchema)]
 pub struct Menu {
     date: NaiveDate,
@@ -28,22 +57,123 @@ impl Menu {
             .collect::<Vec<_>>();
 
         if allow_refresh {
-            check_refresh(db, date, canteens, false).await;
+            check_refresh(db, date, canteens, &config::get().filter_canteens, false).await;
         };
 
-        let result = sqlx::query!(r#"SELECT name, array_agg(DISTINCT canteen ORDER BY canteen) AS "canteens!", dish_type AS "dish_type: DishType", image_src, price_students, price_employees, price_guests, vegan, vegetarian 
+        let rows = sqlx::query_as!(
+            MenuRow,
+            r#"SELECT name, array_agg(DISTINCT canteen ORDER BY canteen) AS "canteens!", dish_type AS "dish_type: DishType", image_src, price_students, price_employees, price_guests, vegan, vegetarian
                 FROM meals WHERE date = $1 AND canteen = ANY($2) AND is_latest = TRUE
                 GROUP BY name, dish_type, image_src, price_students, price_employees, price_guests, vegan, vegetarian
-                ORDER BY name"#, 
+                ORDER BY name"#,
                 date, &canteens_str)
             .fetch_all(db)
             .await?;
 
+        Ok(Self::from_rows(date, rows))
+    }
+
+    /// Get the menu of the given canteens at the given date as it was known at the given time,
+    /// without refreshing it.
+    pub async fn query_at(
+        db: &PgPool,
+        date: NaiveDate,
+        canteens: &[Canteen],
+        at: DateTime<Utc>,
+    ) -> sqlx::Result<Self> {
+        let rows = sqlx::query_as!(
+            MenuRow,
+            r#"SELECT name, array_agg(DISTINCT canteen ORDER BY canteen) AS "canteens!", dish_type AS "dish_type: DishType", image_src, price_students, price_employees, price_guests, vegan, vegetarian
+                FROM meals WHERE date = $1 AND canteen = ANY($2) AND refreshed_at <= $3 AND (replaced_at IS NULL OR replaced_at > $3)
+                GROUP BY name, dish_type, image_src, price_students, price_employees, price_guests, vegan, vegetarian
+                ORDER BY name"#,
+            date,
+            &canteens
+                .iter()
+                .map(|c| c.get_identifier().to_string())
+                .collect::<Vec<_>>(),
+            at,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(Self::from_rows(date, rows))
+    }
+
+    /// Get all meals of the given canteens at the given date, including replaced ones, ordered by
+    /// name.
+    pub(crate) async fn query_versions(
+        db: &PgPool,
+        date: NaiveDate,
+        canteens: &[Canteen],
+    ) -> sqlx::Result<Vec<MealVersion>> {
+        sqlx::query_as!(
+            MealVersion,
+            r#"SELECT name, canteen, dish_type AS "dish_type: DishType", image_src, price_students, price_employees, price_guests, vegan, vegetarian, refreshed_at, replaced_at
+                FROM meals WHERE date = $1 AND canteen = ANY($2)
+                ORDER BY name, canteen"#,
+            date,
+            &canteens
+                .iter()
+                .map(|c| c.get_identifier().to_string())
+                .collect::<Vec<_>>(),
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Get the menu as it was known at the given time from all versions of its meals, like
+    /// [`Menu::query_at`] does in the database.
+    pub(crate) fn from_versions_at(
+        date: NaiveDate,
+        meals: &[MealVersion],
+        at: DateTime<Utc>,
+    ) -> Self {
+        let mut rows: Vec<MenuRow> = Vec::new();
+        for meal in meals
+            .iter()
+            .filter(|meal| meal.refreshed_at <= at && meal.replaced_at.is_none_or(|t| t > at))
+        {
+            let existing = rows.iter_mut().find(|row| {
+                row.name == meal.name
+                    && row.dish_type == meal.dish_type
+                    && row.image_src == meal.image_src
+                    && row.price_students == meal.price_students
+                    && row.price_employees == meal.price_employees
+                    && row.price_guests == meal.price_guests
+                    && row.vegan == meal.vegan
+                    && row.vegetarian == meal.vegetarian
+            });
+            match existing {
+                Some(row) => {
+                    if !row.canteens.contains(&meal.canteen) {
+                        row.canteens.push(meal.canteen.clone());
+                        row.canteens.sort();
+                    }
+                }
+                None => rows.push(MenuRow {
+                    name: meal.name.clone(),
+                    canteens: vec![meal.canteen.clone()],
+                    dish_type: meal.dish_type,
+                    image_src: meal.image_src.clone(),
+                    price_students: meal.price_students,
+                    price_employees: meal.price_employees,
+                    price_guests: meal.price_guests,
+                    vegan: meal.vegan,
+                    vegetarian: meal.vegetarian,
+                }),
+            }
+        }
+
+        Self::from_rows(date, rows)
+    }
+
+    fn from_rows(date: NaiveDate, rows: Vec<MenuRow>) -> Self {
         let mut main_dishes = Vec::new();
         let mut side_dishes = Vec::new();
         let mut desserts = Vec::new();
 
-        for row in result {
+        for row in rows {
             let dish = Dish {
                 name: row.name,
                 image_src: row.image_src,
@@ -70,12 +200,30 @@ impl Menu {
             }
         }
 
-        Ok(Self {
+        Self {
             date,
             main_dishes,
             side_dishes,
             desserts,
-        })
+        }
+    }
+
+    /// Get the time the menu of the given canteens at the given date was last scraped.
+    pub async fn last_modified(
+        db: &PgPool,
+        date: NaiveDate,
+        canteens: &[Canteen],
+    ) -> sqlx::Result<Option<DateTime<Utc>>> {
+        sqlx::query_scalar!(
+            "SELECT max(scraped_at) FROM canteens_scraped WHERE canteen = ANY($1) AND scraped_for = $2",
+            &canteens
+                .iter()
+                .map(|c| c.get_identifier().to_string())
+                .collect::<Vec<_>>(),
+            date
+        )
+        .fetch_one(db)
+        .await
     }
 
     pub fn get_main_dishes(&self) -> &[Dish] {
@@ -90,6 +238,14 @@ impl Menu {
         &self.desserts
     }
 
+    /// Iterate over all dishes of the menu, regardless of their type.
+    pub fn dishes(&self) -> impl Iterator<Item = &Dish> {
+        self.main_dishes
+            .iter()
+            .chain(&self.side_dishes)
+            .chain(&self.desserts)
+    }
+
     pub fn merged(self, other: Self) -> Self {
         let mut main_dishes = self.main_dishes;
         let mut side_dishes = self.side_dishes;
@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use itertools::Itertools as _;
+use lru::LruCache;
+use shared::Canteen;
+use sqlx::PgPool;
+
+use crate::{metrics, Menu};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MenuCacheKey {
+    canteens: Vec<Canteen>,
+    date: NaiveDate,
+    allow_refresh: bool,
+}
+
+#[derive(Debug)]
+pub struct CachedMenu {
+    pub menu: Menu,
+    pub last_modified: Option<DateTime<Utc>>,
+    cached_at: Instant,
+}
+
+/// Bounded in-memory cache of menus, invalidated by menu change notifications.
+#[derive(Debug)]
+pub struct MenuCache {
+    entries: Mutex<LruCache<MenuCacheKey, Arc<CachedMenu>>>,
+    ttl: Duration,
+    /// Incremented on every invalidation, so that menus queried before an invalidation are not
+    /// stored afterwards.
+    generation: AtomicU64,
+    /// Locks held while querying a menu, so that concurrent misses of the same menu (e.g. of all
+    /// menu streams after a change) share a single query.
+    in_flight: Mutex<HashMap<MenuCacheKey, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl MenuCache {
+    pub fn new(capacity: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            ttl,
+            generation: AtomicU64::new(0),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the menu of the given canteens at the given date, querying the database on a cache miss.
+    ///
+    /// The menu is only refreshed on a cache miss, so a refresh that becomes due while the menu
+    /// is cached is delayed by up to the TTL. Menus changed by a refresh are invalidated.
+    pub async fn get_or_query(
+        &self,
+        db: &PgPool,
+        date: NaiveDate,
+        canteens: &[Canteen],
+        allow_refresh: bool,
+    ) -> sqlx::Result<Arc<CachedMenu>> {
+        let key = MenuCacheKey {
+            canteens: canteens.iter().copied().sorted().dedup().collect(),
+            date,
+            allow_refresh,
+        };
+
+        if let Some(cached) = self.get(&key) {
+            metrics::record_menu_cache_request(true);
+            return Ok(cached);
+        }
+
+        let query_lock = {
+            let mut in_flight = self.in_flight.lock().expect("menu cache lock poisoned");
+            // drop locks nobody waits for anymore
+            in_flight.retain(|_, lock| Arc::strong_count(lock) > 1);
+            in_flight.entry(key.clone()).or_default().clone()
+        };
+        let _query_guard = query_lock.lock().await;
+
+        // the menu may have been queried while waiting for the lock
+        if let Some(cached) = self.get(&key) {
+            metrics::record_menu_cache_request(true);
+            return Ok(cached);
+        }
+        metrics::record_menu_cache_request(false);
+
+        let generation = self.generation.load(Ordering::Acquire);
+
+        let menu = Menu::query(db, date, &key.canteens, allow_refresh).await?;
+        let last_modified = Menu::last_modified(db, date, &key.canteens).await?;
+        let cached = Arc::new(CachedMenu {
+            menu,
+            last_modified,
+            cached_at: Instant::now(),
+        });
+
+        let mut entries = self.entries.lock().expect("menu cache lock poisoned");
+        if self.generation.load(Ordering::Acquire) == generation
+            && entries
+                .push(key.clone(), cached.clone())
+                .is_some_and(|(evicted, _)| evicted != key)
+        {
+            metrics::record_menu_cache_evictions(1);
+        }
+
+        Ok(cached)
+    }
+
+    fn get(&self, key: &MenuCacheKey) -> Option<Arc<CachedMenu>> {
+        let mut entries = self.entries.lock().expect("menu cache lock poisoned");
+        match entries.get(key) {
+            Some(cached) if cached.cached_at.elapsed() < self.ttl => Some(cached.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Remove all cached menus containing the given canteen at the given date.
+    pub fn invalidate(&self, date: NaiveDate, canteen: Canteen) {
+        let mut entries = self.entries.lock().expect("menu cache lock poisoned");
+        self.generation.fetch_add(1, Ordering::AcqRel);
+
+        let stale_keys = entries
+            .iter()
+            .filter(|(key, _)| key.date == date && key.canteens.contains(&canteen))
+            .map(|(key, _)| key.clone())
+            .collect_vec();
+        metrics::record_menu_cache_invalidations(stale_keys.len() as u64);
+        for key in stale_keys {
+            entries.pop(&key);
+        }
+    }
+
+    /// Remove all cached menus.
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().expect("menu cache lock poisoned");
+        self.generation.fetch_add(1, Ordering::AcqRel);
+
+        metrics::record_menu_cache_invalidations(entries.len() as u64);
+        entries.clear();
+    }
+
+    /// Update the gauges of the cache size, which are only sampled when rendering the metrics.
+    pub(crate) fn record_size(&self) {
+        let entries = self.entries.lock().expect("menu cache lock poisoned");
+        metrics::record_menu_cache_size(entries.len(), entries.cap().get());
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use shared::Canteen;
+use sqlx::PgPool;
+
+use crate::{Dish, Menu};
+
+/// All versions of the menu of canteen(s) at a date, as scraped over time.
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct MenuHistory {
+    date: NaiveDate,
+    /// The time the menu was last scraped, which confirms the newest version until then.
+    last_scraped_at: Option<DateTime<Utc>>,
+    /// The versions of the menu, oldest first.
+    versions: Vec<MenuVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct MenuVersion {
+    /// The time this version was stored, when the scrape that first returned it was saved (or
+    /// the time of the first scrape, if it found no dishes).
+    scraped_at: DateTime<Utc>,
+    /// The time the next version was stored, if any.
+    replaced_at: Option<DateTime<Utc>>,
+    menu: Menu,
+    /// The changes compared to the previous version, or all dishes for the first version.
+    changes: MenuDiff,
+}
+
+/// Changes between two versions of a menu.
+#[derive(Debug, Clone, Default, Serialize, utoipa::ToSchema)]
+pub struct MenuDiff {
+    added: Vec<Dish>,
+    removed: Vec<Dish>,
+    /// Dishes with the same name, but different details (e.g. prices or canteens).
+    changed: Vec<DishChange>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct DishChange {
+    before: Dish,
+    after: Dish,
+}
+
+impl MenuHistory {
+    /// Reconstruct the versions of the menu of the given canteens at the given date from the
+    /// replaced dishes that are kept in the database.
+    pub async fn query(db: &PgPool, date: NaiveDate, canteens: &[Canteen]) -> sqlx::Result<Self> {
+        let meals = Menu::query_versions(db, date, canteens).await?;
+        let first_scraped_at = sqlx::query_scalar!(
+            "SELECT MIN(scraped_at) FROM canteens_scraped WHERE scraped_for = $1 AND canteen = ANY($2)",
+            date,
+            &canteens
+                .iter()
+                .map(|c| c.get_identifier().to_string())
+                .collect::<Vec<_>>(),
+        )
+        .fetch_one(db)
+        .await?;
+
+        // the menu only changes when dishes are inserted or replaced, but the first scrape may
+        // also have found no dishes at all
+        let timestamps = meals
+            .iter()
+            .flat_map(|meal| [Some(meal.refreshed_at), meal.replaced_at])
+            .chain([first_scraped_at])
+            .flatten()
+            .collect::<BTreeSet<_>>();
+
+        let mut versions: Vec<MenuVersion> = Vec::with_capacity(timestamps.len());
+        for scraped_at in timestamps {
+            let menu = Menu::from_versions_at(date, &meals, scraped_at);
+            let changes = match versions.last() {
+                Some(previous) => MenuDiff::between(&previous.menu, &menu),
+                None => MenuDiff::between(&Menu::default(), &menu),
+            };
+
+            // changes at multiple canteens may cancel each other out in the merged menu
+            if changes.is_empty() && !versions.is_empty() {
+                continue;
+            }
+            if let Some(previous) = versions.last_mut() {
+                previous.replaced_at = Some(scraped_at);
+            }
+
+            versions.push(MenuVersion {
+                scraped_at,
+                replaced_at: None,
+                menu,
+                changes,
+            });
+        }
+
+        Ok(Self {
+            date,
+            last_scraped_at: Menu::last_modified(db, date, canteens).await?,
+            versions,
+        })
+    }
+
+    pub fn last_scraped_at(&self) -> Option<DateTime<Utc>> {
+        self.last_scraped_at
+    }
+}
+
+impl MenuDiff {
+    pub fn between(old: &Menu, new: &Menu) -> Self {
+        let mut removed = old
+            .dishes()
+            .filter(|dish| !new.dishes().any(|other| other == *dish))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut added = new
+            .dishes()
+            .filter(|dish| !old.dishes().any(|other| other == *dish))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // a dish may be listed once per price at different canteens, so entries of the same
+        // dish are preferably matched at the same canteens
+        let mut changed = Vec::new();
+        added.retain(|after| {
+            let same_canteen = removed.iter().position(|before| {
+                before.name == after.name
+                    && before
+                        .canteens
+                        .iter()
+                        .any(|canteen| after.canteens.contains(canteen))
+            });
+            match same_canteen.or_else(|| removed.iter().position(|before| before.name == after.name)) {
+                Some(index) => {
+                    changed.push(DishChange {
+                        before: removed.remove(index),
+                        after: after.clone(),
+                    });
+                    false
+                }
+                None => true,
+            }
+        });
+
+        Self {
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
@@ -0,0 +1,130 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::web::Bytes;
+use chrono::NaiveDate;
+use futures::Stream;
+use shared::Canteen;
+use sqlx::PgPool;
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
+
+use crate::{
+    notifier::{MenuEvent, MenuEventReceiver},
+    MenuCache,
+};
+
+/// Interval in which a comment is sent to keep idle connections (and proxies) alive.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+struct MenuStreamState {
+    db: PgPool,
+    menu_cache: Arc<MenuCache>,
+    events: MenuEventReceiver,
+    canteens: Vec<Canteen>,
+    date: NaiveDate,
+    /// Whether the stream switches to the next day at midnight.
+    follow_today: bool,
+    allow_refresh: bool,
+    /// The last menu sent, serialized, to avoid sending the same menu twice.
+    last_sent: Option<String>,
+    keep_alive: Interval,
+}
+
+/// Create a stream of server-sent events containing the menu of the given canteens, sending the
+/// current menu first and the updated menu whenever it changes.
+///
+/// If no date is given, the menu of the current day is streamed. Menus are queried through the
+/// menu cache, so that all streams of a changed menu share a single query.
+pub fn menu_updates(
+    db: PgPool,
+    menu_cache: Arc<MenuCache>,
+    events: MenuEventReceiver,
+    canteens: Vec<Canteen>,
+    date: Option<NaiveDate>,
+    allow_refresh: bool,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let mut keep_alive = interval_at(Instant::now() + KEEP_ALIVE_INTERVAL, KEEP_ALIVE_INTERVAL);
+    keep_alive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let state = MenuStreamState {
+        db,
+        menu_cache,
+        events,
+        canteens,
+        date: date.unwrap_or_else(today),
+        follow_today: date.is_none(),
+        allow_refresh,
+        last_sent: None,
+        keep_alive,
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        if state.last_sent.is_none()
+            && let Some(event) = state.next_menu_event().await
+        {
+            return Some((Ok(event), state));
+        }
+
+        loop {
+            let update = tokio::select! {
+                event = state.events.recv() => match event {
+                    Some(MenuEvent::Changed(changed)) => {
+                        changed.date == state.date && state.canteens.contains(&changed.canteen)
+                    }
+                    Some(MenuEvent::Resync) => true,
+                    None => return None,
+                },
+                _ = state.keep_alive.tick() => {
+                    if state.follow_today && state.date != today() {
+                        state.date = today();
+                        true
+                    } else {
+                        return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), state));
+                    }
+                }
+            };
+
+            if update && let Some(event) = state.next_menu_event().await {
+                return Some((Ok(event), state));
+            }
+        }
+    })
+}
+
+impl MenuStreamState {
+    /// Query the current menu and format it as an event, if it differs from the last one sent.
+    async fn next_menu_event(&mut self) -> Option<Bytes> {
+        // the initial menu may be refreshed, updates are only sent after the database changed
+        let allow_refresh = self.allow_refresh && self.last_sent.is_none();
+
+        let cached = match self
+            .menu_cache
+            .get_or_query(&self.db, self.date, &self.canteens, allow_refresh)
+            .await
+        {
+            Ok(cached) => cached,
+            Err(err) => {
+                tracing::error!("Failed to query database: {err:?}");
+                return None;
+            }
+        };
+        let data = match serde_json::to_string(&cached.menu) {
+            Ok(data) => data,
+            Err(err) => {
+                tracing::error!("Failed to serialize menu: {err}");
+                return None;
+            }
+        };
+
+        if self.last_sent.as_ref() == Some(&data) {
+            None
+        } else {
+            let event = Bytes::from(format!("event: menu\ndata: {data}\n\n"));
+            self.last_sent = Some(data);
+            Some(event)
+        }
+    }
+}
+
+fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use sqlx::PgPool;
+
+const HTTP_REQUESTS: &str = "http_requests_total";
+const HTTP_REQUEST_DURATION: &str = "http_request_duration_seconds";
+const RATE_LIMIT_REJECTIONS: &str = "rate_limit_rejections_total";
+const DB_POOL_CONNECTIONS: &str = "db_pool_connections";
+const DB_POOL_IDLE_CONNECTIONS: &str = "db_pool_idle_connections";
+const DB_POOL_MAX_CONNECTIONS: &str = "db_pool_max_connections";
+const MENU_CACHE_REQUESTS: &str = "menu_cache_requests_total";
+const MENU_CACHE_INVALIDATIONS: &str = "menu_cache_invalidations_total";
+const MENU_CACHE_EVICTIONS: &str = "menu_cache_evictions_total";
+const MENU_CACHE_ENTRIES: &str = "menu_cache_entries";
+const MENU_CACHE_CAPACITY: &str = "menu_cache_capacity";
+
+/// Interval in which the recorder drains its histograms.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Install the Prometheus recorder collecting the metrics of the API and the scraper.
+pub fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    let handle = mensa_upb_scraper::metrics::install_recorder()?;
+
+    describe_counter!(HTTP_REQUESTS, "HTTP requests, by method, route and status");
+    describe_histogram!(
+        HTTP_REQUEST_DURATION,
+        Unit::Seconds,
+        "Time it took to answer HTTP requests, by method and route"
+    );
+    describe_counter!(
+        RATE_LIMIT_REJECTIONS,
+        "Requests rejected for exceeding their rate limit, by quota"
+    );
+    describe_gauge!(DB_POOL_CONNECTIONS, "Open database connections");
+    describe_gauge!(DB_POOL_IDLE_CONNECTIONS, "Idle database connections");
+    describe_gauge!(
+        DB_POOL_MAX_CONNECTIONS,
+        "Maximum number of database connections"
+    );
+    describe_counter!(
+        MENU_CACHE_REQUESTS,
+        "Menu requests to the in-memory menu cache, by result (hit or miss)"
+    );
+    describe_counter!(
+        MENU_CACHE_INVALIDATIONS,
+        "Menu cache entries removed because the underlying menu changed"
+    );
+    describe_counter!(
+        MENU_CACHE_EVICTIONS,
+        "Menu cache entries removed to make room for new ones"
+    );
+    describe_gauge!(MENU_CACHE_ENTRIES, "Entries in the menu cache");
+    describe_gauge!(
+        MENU_CACHE_CAPACITY,
+        "Maximum number of entries in the menu cache"
+    );
+
+    Ok(handle)
+}
+
+/// Periodically run the upkeep of the recorder, so its histograms do not grow unboundedly.
+pub async fn run_metrics_upkeep(handle: PrometheusHandle) {
+    loop {
+        tokio::time::sleep(UPKEEP_INTERVAL).await;
+        handle.run_upkeep();
+    }
+}
+
+/// Middleware recording the count and duration of requests per route.
+pub async fn record_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    // use the route pattern instead of the path to keep the number of label values bounded
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let res = next.call(req).await?;
+
+    let status = res.status().as_u16().to_string();
+    counter!(HTTP_REQUESTS, "method" => method.clone(), "route" => route.clone(), "status" => status)
+        .increment(1);
+    histogram!(HTTP_REQUEST_DURATION, "method" => method, "route" => route).record(start.elapsed());
+
+    Ok(res)
+}
+
+pub(crate) fn record_rate_limit_rejection(quota: &'static str) {
+    counter!(RATE_LIMIT_REJECTIONS, "quota" => quota).increment(1);
+}
+
+/// Update the gauges of the database pool, which are only sampled when rendering the metrics.
+pub(crate) fn record_db_pool(db: &PgPool) {
+    gauge!(DB_POOL_CONNECTIONS).set(db.size());
+    gauge!(DB_POOL_IDLE_CONNECTIONS).set(db.num_idle() as f64);
+    gauge!(DB_POOL_MAX_CONNECTIONS).set(db.options().get_max_connections());
+}
+
+pub(crate) fn record_menu_cache_request(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    counter!(MENU_CACHE_REQUESTS, "result" => result).increment(1);
+}
+
+pub(crate) fn record_menu_cache_invalidations(count: u64) {
+    counter!(MENU_CACHE_INVALIDATIONS).increment(count);
+}
+
+pub(crate) fn record_menu_cache_evictions(count: u64) {
+    counter!(MENU_CACHE_EVICTIONS).increment(count);
+}
+
+pub(crate) fn record_menu_cache_size(entries: usize, capacity: usize) {
+    gauge!(MENU_CACHE_ENTRIES).set(entries as f64);
+    gauge!(MENU_CACHE_CAPACITY).set(capacity as f64);
+}
@@ -0,0 +1,118 @@
+use std::{sync::Arc, time::Duration};
+
+use shared::{MenuChangedNotification, MENU_CHANGED_CHANNEL};
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::MenuCache;
+
+/// Number of events buffered per subscriber before it is considered lagging.
+const EVENT_BUFFER_SIZE: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuEvent {
+    /// The stored menu of a canteen changed.
+    Changed(MenuChangedNotification),
+    /// Notifications may have been missed, so any menu could have changed.
+    Resync,
+}
+
+/// Fans out [`MENU_CHANGED_CHANNEL`] notifications from the database to all subscribers.
+///
+/// Changed menus are invalidated in the menu cache before the subscribers are notified, so that
+/// subscribers querying the menu through the cache get the changed one.
+#[derive(Debug)]
+pub struct MenuChangeNotifier {
+    sender: broadcast::Sender<MenuEvent>,
+    menu_cache: Arc<MenuCache>,
+}
+
+pub struct MenuEventReceiver {
+    receiver: broadcast::Receiver<MenuEvent>,
+}
+
+impl MenuChangeNotifier {
+    pub fn new(menu_cache: Arc<MenuCache>) -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self { sender, menu_cache }
+    }
+
+    pub fn subscribe(&self) -> MenuEventReceiver {
+        MenuEventReceiver {
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    /// Listen for menu change notifications and forward them to all subscribers, reconnecting
+    /// whenever the connection to the database is lost.
+    pub async fn listen(self: Arc<Self>, db: PgPool) {
+        loop {
+            if let Err(err) = self.listen_once(&db).await {
+                tracing::error!("Failed to listen for menu changes: {err}");
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    async fn listen_once(&self, db: &PgPool) -> sqlx::Result<()> {
+        let mut listener = PgListener::connect_with(db).await?;
+        listener.listen(MENU_CHANGED_CHANNEL).await?;
+
+        // changes made while not listening would otherwise go unnoticed
+        self.send(MenuEvent::Resync);
+
+        loop {
+            match listener.try_recv().await? {
+                Some(notification) => {
+                    match serde_json::from_str::<MenuChangedNotification>(notification.payload()) {
+                        Ok(changed) => {
+                            tracing::debug!(
+                                "Menu of {:?} at {} changed",
+                                changed.canteen,
+                                changed.date
+                            );
+                            self.send(MenuEvent::Changed(changed));
+                        }
+                        Err(err) => {
+                            tracing::warn!("Received malformed menu change notification: {err}");
+                            self.send(MenuEvent::Resync);
+                        }
+                    }
+                }
+                None => {
+                    // the listener reconnects on the next call, but notifications may have been lost
+                    tracing::warn!("Lost connection while listening for menu changes");
+                    self.send(MenuEvent::Resync);
+                }
+            }
+        }
+    }
+
+    fn send(&self, event: MenuEvent) {
+        match &event {
+            MenuEvent::Changed(changed) => {
+                self.menu_cache.invalidate(changed.date, changed.canteen)
+            }
+            MenuEvent::Resync => self.menu_cache.clear(),
+        }
+
+        // sending only fails if there are no subscribers, in which case nobody is interested
+        self.sender.send(event).ok();
+    }
+}
+
+impl MenuEventReceiver {
+    /// Receive the next event, or `None` if the notifier was dropped.
+    ///
+    /// If the receiver fell behind and events were dropped, [`MenuEvent::Resync`] is returned.
+    pub async fn recv(&mut self) -> Option<MenuEvent> {
+        match self.receiver.recv().await {
+            Ok(event) => Some(event),
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Menu event subscriber lagged behind by {skipped} events");
+                Some(MenuEvent::Resync)
+            }
+            Err(RecvError::Closed) => None,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use sqlx::PgPool;
+
+use crate::dish_search::{self, DishSuggestion};
+
+/// Maximum number of cached prefixes.
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(1024).expect("capacity is not zero");
+/// Time after which the suggestions of a prefix are queried again. The ranking only shifts
+/// slowly as dishes are served, so it does not need to be invalidated on menu changes.
+const TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SuggestionCacheKey {
+    prefix: String,
+    limit: i64,
+}
+
+#[derive(Debug)]
+struct CachedSuggestions {
+    suggestions: Arc<Vec<DishSuggestion>>,
+    cached_at: Instant,
+}
+
+/// Bounded in-memory cache of dish name suggestions, as autocompletion queries the same short
+/// prefixes over and over.
+#[derive(Debug)]
+pub struct SuggestionCache {
+    entries: Mutex<LruCache<SuggestionCacheKey, CachedSuggestions>>,
+}
+
+impl SuggestionCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(CAPACITY)),
+        }
+    }
+
+    /// Get the suggestions for the given prefix, querying the database on a cache miss.
+    pub async fn get_or_query(
+        &self,
+        db: &PgPool,
+        prefix: &str,
+        limit: i64,
+    ) -> sqlx::Result<Arc<Vec<DishSuggestion>>> {
+        let key = SuggestionCacheKey {
+            prefix: dish_search::normalize_dish_name(prefix),
+            limit,
+        };
+
+        if let Some(suggestions) = self.get(&key) {
+            return Ok(suggestions);
+        }
+
+        let suggestions = Arc::new(dish_search::suggest_dishes(db, &key.prefix, limit).await?);
+
+        self.entries
+            .lock()
+            .expect("suggestion cache lock poisoned")
+            .push(
+                key,
+                CachedSuggestions {
+                    suggestions: suggestions.clone(),
+                    cached_at: Instant::now(),
+                },
+            );
+
+        Ok(suggestions)
+    }
+
+    fn get(&self, key: &SuggestionCacheKey) -> Option<Arc<Vec<DishSuggestion>>> {
+        let mut entries = self.entries.lock().expect("suggestion cache lock poisoned");
+        match entries.get(key) {
+            Some(cached) if cached.cached_at.elapsed() < TTL => Some(cached.suggestions.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+impl Default for SuggestionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::future;
+use mensa_upb_scraper::{check_refresh, config, upcoming_days};
+use shared::Canteen;
+use sqlx::PgPool;
+use strum::IntoEnumIterator as _;
+
+/// Minimum time between two refreshes requested by clients. Menus are only refreshed if they are
+/// due anyway, so more frequent refreshes would only check that over and over.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Default)]
+struct RefreshState {
+    running: bool,
+    last_started: Option<Instant>,
+}
+
+/// Refreshes the menus of the upcoming days in the background on behalf of requests, running at
+/// most one refresh at a time and at most one per [`MIN_REFRESH_INTERVAL`], so that clients
+/// cannot make the API scrape the canteens' websites over and over.
+#[derive(Debug, Default)]
+pub struct UpcomingMenuRefresher {
+    state: Mutex<RefreshState>,
+}
+
+impl UpcomingMenuRefresher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start refreshing the menus of all canteens for the upcoming days in the background, unless
+    /// a refresh is running or was started recently.
+    pub fn trigger(self: Arc<Self>, db: &PgPool) {
+        {
+            let mut state = self.state.lock().expect("refresh state lock poisoned");
+            if state.running
+                || state
+                    .last_started
+                    .is_some_and(|at| at.elapsed() < MIN_REFRESH_INTERVAL)
+            {
+                return;
+            }
+            state.running = true;
+            state.last_started = Some(Instant::now());
+        }
+
+        let db = db.clone();
+        tokio::spawn(async move {
+            let canteens = Canteen::iter().collect::<Vec<_>>();
+            future::join_all(upcoming_days().map(|date| {
+                check_refresh(&db, date, &canteens, &config::get().filter_canteens, false)
+            }))
+            .await;
+
+            self.state
+                .lock()
+                .expect("refresh state lock poisoned")
+                .running = false;
+        });
+    }
+}
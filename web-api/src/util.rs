@@ -1,13 +1,62 @@
 use std::str::FromStr as _;
 
+use chrono::{Datelike as _, Duration, Months, NaiveDate};
+use itertools::Itertools as _;
+use serde::{Deserialize, Serialize};
 use shared::Canteen;
 
-pub fn parse_canteens_comma_separated(s: &str) -> Vec<Result<Canteen, String>> {
-    s.split(',').map(Canteen::from_str).collect()
+use crate::error::ApiError;
+
+/// Parse a comma separated list of canteen identifiers, failing with all invalid identifiers.
+pub fn parse_canteens_comma_separated(s: &str) -> Result<Vec<Canteen>, ApiError> {
+    let (canteens, invalid): (Vec<_>, Vec<_>) = s
+        .split(',')
+        .map(|id| Canteen::from_str(id).map_err(|_| id.to_string()))
+        .partition_result();
+
+    if invalid.is_empty() {
+        Ok(canteens)
+    } else {
+        Err(ApiError::InvalidCanteen(invalid))
+    }
+}
+
+/// Length of the periods data is aggregated over.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize, utoipa::ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    /// Weeks starting on Monday.
+    Week,
+    /// Calendar months.
+    #[default]
+    Month,
 }
 
-#[expect(dead_code)]
-#[derive(utoipa::ToSchema)]
-pub(crate) struct GenericServerError {
-    error: &'static str,
+impl Granularity {
+    /// Get the field passed to `date_trunc` to get the start of the period of a date.
+    pub fn date_trunc_field(self) -> &'static str {
+        match self {
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+
+    /// Get the first day of the period containing the date, like `date_trunc` does.
+    pub fn period_start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+            Self::Month => date.with_day(1).expect("every month has a first day"),
+        }
+    }
+
+    /// Get the last day of the period containing the date.
+    pub fn period_end(self, date: NaiveDate) -> NaiveDate {
+        let start = self.period_start(date);
+        match self {
+            Self::Week => start + Duration::days(6),
+            Self::Month => start + Months::new(1) - Duration::days(1),
+        }
+    }
 }
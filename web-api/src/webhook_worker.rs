@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use mensa_upb_scraper::webhook;
+use sqlx::PgPool;
+
+use crate::notifier::{MenuEvent, MenuEventReceiver};
+
+/// Interval in which due webhook deliveries (e.g. retries) are sent without a menu change.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Send webhook deliveries whenever a menu changed and periodically retry failed ones.
+pub async fn deliver_webhooks(db: PgPool, mut events: MenuEventReceiver) {
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(MenuEvent::Changed(_) | MenuEvent::Resync) => {}
+                None => return,
+            },
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+
+        match webhook::deliver_pending(&db).await {
+            Ok(0) => {}
+            Ok(delivered) => tracing::debug!("Delivered {delivered} webhook payloads"),
+            Err(err) => tracing::error!("Failed to deliver webhooks: {err}"),
+        }
+    }
+}